@@ -0,0 +1,92 @@
+use std::{env, fs, path::Path};
+
+use serde_json::Value;
+
+/// Generates the opcode decode tables from Opcodes.json, so that the CPU, the disassembler and the trace output all
+/// read lengths, cycle counts, operands and flag effects from the same place.
+fn main()
+{
+    println!("cargo:rerun-if-changed=Opcodes.json");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let json = fs::read_to_string("Opcodes.json").expect("Failed to read Opcodes.json");
+    let root: Value = serde_json::from_str(&json).expect("Opcodes.json is not valid JSON");
+
+    let mut output = String::new();
+
+    output.push_str(&generate_table("UNPREFIXED", &root["unprefixed"]));
+    output.push_str(&generate_table("CB_PREFIXED", &root["cbprefixed"]));
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("opcodes.rs"), output).unwrap();
+}
+
+fn generate_table(name: &str, table: &Value) -> String
+{
+    let mut output = format!("/// Generated from Opcodes.json\npub const {}: [Opcode; 256] = [\n", name);
+
+    for opcode in 0..=0xFFu8
+    {
+        let entry = &table[format!("0x{:0>2X}", opcode)];
+
+        if entry.is_null()
+        {
+            panic!("Opcodes.json is missing an entry for {} 0x{:0>2X}", name, opcode);
+        }
+
+        let cycles: Vec<String> = entry["cycles"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(| c | c.as_u64().unwrap().to_string())
+            .collect();
+
+        // Unconditional instructions only list one cost, so repeat it for the not-taken slot
+        let taken = &cycles[0];
+        let not_taken = cycles.get(1).unwrap_or(taken);
+
+        let operands: Vec<String> = entry["operands"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(| o | format!(
+                "Operand {{ name: {:?}, bytes: {}, immediate: {}, increment: {}, decrement: {} }}",
+                o["name"].as_str().unwrap(),
+                o["bytes"].as_u64().unwrap_or(0),
+                o["immediate"].as_bool().unwrap(),
+                o["increment"].as_bool().unwrap_or(false),
+                o["decrement"].as_bool().unwrap_or(false)
+            ))
+            .collect();
+
+        let flags = &entry["flags"];
+
+        output.push_str(&format!(
+            "    Opcode {{ mnemonic: {:?}, bytes: {}, cycles: [{}, {}], operands: &[{}], flags: [{}, {}, {}, {}] }},\n",
+            entry["mnemonic"].as_str().unwrap(),
+            entry["bytes"].as_u64().unwrap(),
+            taken,
+            not_taken,
+            operands.join(", "),
+            flag_effect(&flags["Z"]),
+            flag_effect(&flags["N"]),
+            flag_effect(&flags["H"]),
+            flag_effect(&flags["C"])
+        ));
+    }
+
+    output.push_str("];\n\n");
+
+    output
+}
+
+fn flag_effect(value: &Value) -> &'static str
+{
+    match value.as_str().unwrap()
+    {
+        "-" => "FlagEffect::Unaffected",
+        "0" => "FlagEffect::Reset",
+        "1" => "FlagEffect::Set",
+        _ => "FlagEffect::Affected"
+    }
+}
@@ -0,0 +1,167 @@
+#![allow(dead_code)]
+
+use std::{ffi::OsString, fs, io, path::{Path, PathBuf}};
+
+use argentous_oxide::component::cartridge::{self, fix, CartridgeError};
+
+/// Options for `fix`
+struct FixOptions
+{
+    rom: PathBuf,
+    /// Writes here instead of over the input
+    output: Option<PathBuf>,
+    /// Also overwrites the Nintendo logo
+    logo: bool,
+    /// Reports what would change without writing anything
+    dry_run: bool
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> FixOptions
+{
+    let mut rom = None;
+    let mut output = None;
+    let mut logo = false;
+    let mut dry_run = false;
+
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
+        {
+            "-o" | "--output" => output = match args.next()
+            {
+                Some(path) => Some(PathBuf::from(path)),
+                None => crate::usage_error(format!("Expected a value after \"{}\".", arg))
+            },
+            "--logo" => logo = true,
+            "--dry-run" => dry_run = true,
+            _ if !arg.starts_with('-') && rom.is_none() => rom = Some(PathBuf::from(arg)),
+            _ => crate::usage_error(format!("Unexpected argument \"{}\".", arg))
+        }
+    }
+
+    match rom
+    {
+        Some(rom) => FixOptions { rom, output, logo, dry_run },
+        None => crate::usage_error("No ROM given.".to_string())
+    }
+}
+
+/// Applies every fix to a ROM image, printing what changed. Returns whether anything did.
+fn fix_rom(rom: &mut Vec<u8>, logo: bool) -> Result<bool, CartridgeError>
+{
+    fix::check_len(rom)?;
+
+    let mut changed = false;
+
+    if logo && fix::fix_logo(rom)
+    {
+        println!("Patched the Nintendo logo.");
+        changed = true;
+    }
+
+    if let Some(size) = fix::fix_size(rom)?
+    {
+        if size.old_len != size.new_len
+        {
+            println!("Padded ROM from {}B to {}B.", size.old_len, size.new_len);
+        }
+
+        if size.old_code != size.new_code
+        {
+            println!("ROM size (0x148): 0x{:0>2X} -> 0x{:0>2X}", size.old_code, size.new_code);
+        }
+
+        changed = true;
+    }
+
+    if let Some((old, new)) = fix::fix_header_checksum(rom)
+    {
+        println!("Header checksum (0x14D): 0x{:0>2X} -> 0x{:0>2X}", old, new);
+        changed = true;
+    }
+
+    if let Some((old, new)) = fix::fix_global_checksum(rom)
+    {
+        println!("Global checksum (0x14E): 0x{:0>4X} -> 0x{:0>4X}", old, new);
+        changed = true;
+    }
+
+    Ok(changed)
+}
+
+/// Writes the ROM next to its destination first and renames it over, so a failed write can't leave the original
+/// half overwritten
+fn write_rom(path: &Path, rom: &[u8]) -> io::Result<()>
+{
+    let mut temp_name = path.file_name().map(OsString::from).unwrap_or_default();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = fs::write(&temp_path, rom).and_then(|()| fs::rename(&temp_path, path));
+
+    if result.is_err()
+    {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
+/// `fix [--logo] [--dry-run] [-o <file>] <rom>`: recomputes both checksums, pads the ROM to a size the header can
+/// declare and updates 0x148 to match, and optionally restores the Nintendo logo, like rgbfix.
+pub fn run(args: impl Iterator<Item = String>)
+{
+    let options = parse_args(args);
+    let path = options.rom.display().to_string();
+
+    let data = match fs::read(&options.rom)
+    {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Failed to read ROM at \"{}\". (Technical error: {})", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let archive = cartridge::is_archive(&data);
+
+    // The fixed ROM is written out bare, so an archive can't be fixed in place
+    let output: &Path = match (&options.output, archive)
+    {
+        (Some(output), _) => output,
+        (None, false) => &options.rom,
+        (None, true) => crate::usage_error(format!("\"{}\" is an archive. Use --output to write the fixed ROM somewhere else.", path))
+    };
+
+    let result = cartridge::extract_rom(data)
+        .map_err(CartridgeError::from)
+        .and_then(| mut rom | fix_rom(&mut rom, options.logo).map(| changed | (rom, changed)));
+
+    let rom = match result
+    {
+        Ok((_, false)) if options.output.is_none() => {
+            println!("Nothing to fix.");
+            return;
+        },
+        Ok((rom, _)) => rom,
+        Err(err) => {
+            eprintln!("Failed to fix ROM at \"{}\". (Technical error: {})", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    if options.dry_run
+    {
+        println!("Dry run, nothing was written.");
+        return;
+    }
+
+    match write_rom(output, &rom)
+    {
+        Ok(()) => println!("Wrote \"{}\" ({}B).", output.display(), rom.len()),
+        Err(err) => {
+            eprintln!("Failed to write \"{}\". (Technical error: {})", output.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
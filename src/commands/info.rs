@@ -0,0 +1,223 @@
+#![allow(dead_code)]
+
+use std::path::Path;
+
+use argentous_oxide::component::{cartridge::{self, header::{self, CartridgeHeader, CgbSupport, Destination, RamSize}}, mbc};
+use serde_json::{json, Value};
+
+/// Everything `info` reports about a ROM
+struct RomInfo
+{
+    header: CartridgeHeader,
+    file_size: usize,
+    computed_header_checksum: u8,
+    computed_global_checksum: u16,
+    /// Whether the cartridge type can be emulated
+    supported: bool
+}
+
+impl RomInfo
+{
+    fn inspect(rom: &[u8]) -> Result<Self, cartridge::CartridgeError>
+    {
+        let header = CartridgeHeader::parse(rom)?;
+
+        Ok(RomInfo
+        {
+            supported: mbc::mbc_for(header.cartridge_type).is_some(),
+            header,
+            file_size: rom.len(),
+            computed_header_checksum: header::header_checksum(rom),
+            computed_global_checksum: header::global_checksum(rom)
+        })
+    }
+
+    fn size_matches(&self) -> bool
+    {
+        self.header.rom_size.bytes() == Some(self.file_size)
+    }
+
+    fn global_checksum_valid(&self) -> bool
+    {
+        self.computed_global_checksum == self.header.global_checksum
+    }
+
+    fn to_json(&self, path: &str) -> Value
+    {
+        let header = &self.header;
+        let cart_type = header.cartridge_type;
+
+        json!({
+            "path": path,
+            "file_size": self.file_size,
+            "title": header.title,
+            "manufacturer_code": header.manufacturer_code,
+            "cgb_support": cgb_name(header.cgb_support),
+            "sgb_supported": header.sgb_supported,
+            "old_licensee_code": header.old_licensee_code,
+            "new_licensee_code": header.new_licensee_code,
+            "cartridge_type": {
+                "code": cart_type.byte(),
+                "name": cart_type.name(),
+                "known": cart_type.is_known(),
+                "supported": self.supported,
+                "ram": cart_type.has_ram(),
+                "battery": cart_type.has_battery(),
+                "timer": cart_type.has_timer(),
+                "rumble": cart_type.has_rumble(),
+                "sensor": cart_type.has_sensor()
+            },
+            "rom_size": {
+                "code": header.rom_size.byte(),
+                "declared": header.rom_size.bytes(),
+                "actual": self.file_size,
+                "matches": self.size_matches()
+            },
+            "ram_size": {
+                "code": header.ram_size.byte(),
+                "bytes": match header.ram_size
+                {
+                    RamSize::Unknown(_) => None,
+                    size => Some(size.bytes())
+                }
+            },
+            "destination": destination_name(header.destination),
+            "version": header.version,
+            "entry_point": header.entry_point,
+            "logo_valid": header.logo_valid,
+            "header_checksum": {
+                "stored": header.header_checksum,
+                "computed": self.computed_header_checksum,
+                "valid": header.header_checksum_valid
+            },
+            "global_checksum": {
+                "stored": header.global_checksum,
+                "computed": self.computed_global_checksum,
+                "valid": self.global_checksum_valid()
+            }
+        })
+    }
+
+    fn print(&self, path: &str)
+    {
+        let header = &self.header;
+        let cart_type = header.cartridge_type;
+
+        let capabilities: Vec<&str> = [
+            (cart_type.has_ram(), "RAM"),
+            (cart_type.has_battery(), "Battery"),
+            (cart_type.has_timer(), "Timer"),
+            (cart_type.has_rumble(), "Rumble"),
+            (cart_type.has_sensor(), "Sensor")
+        ].into_iter().filter(| (present, _) | *present).map(| (_, name) | name).collect();
+
+        println!("File:              {} ({}B)", path, self.file_size);
+        println!("Title:             {}", header.title);
+        println!("Manufacturer:      {}", header.manufacturer_code.as_deref().unwrap_or("None"));
+        println!("CGB support:       {}", cgb_name(header.cgb_support));
+        println!("SGB support:       {}", header.sgb_supported);
+
+        match &header.new_licensee_code
+        {
+            Some(code) => println!("Licensee:          {} (new code)", code),
+            None => println!("Licensee:          0x{:0>2X} (old code)", header.old_licensee_code)
+        }
+
+        println!("Cartridge type:    0x{:0>2X} {} [{}]{}", cart_type.byte(), cart_type.name(), capabilities.join(", "), match self.supported
+        {
+            true => "",
+            false => " (not supported)"
+        });
+
+        match header.rom_size.bytes()
+        {
+            Some(size) if self.size_matches() => println!("ROM size:          0x{:0>2X} {}KiB", header.rom_size.byte(), size / 1024),
+            Some(size) => println!("ROM size:          0x{:0>2X} {}KiB, but the file is {}B (MISMATCH)", header.rom_size.byte(), size / 1024, self.file_size),
+            None => println!("ROM size:          0x{:0>2X} Unknown, the file is {}B (MISMATCH)", header.rom_size.byte(), self.file_size)
+        }
+
+        match header.ram_size
+        {
+            RamSize::Unknown(byte) => println!("RAM size:          0x{:0>2X} Unknown", byte),
+            size => println!("RAM size:          0x{:0>2X} {}KiB", size.byte(), size.bytes() / 1024)
+        }
+
+        println!("Destination:       {}", destination_name(header.destination));
+        println!("Version:           {}", header.version);
+        println!("Entry point:       {:0>2X?}", header.entry_point);
+        println!("Nintendo logo:     {}", pass_fail(header.logo_valid));
+        println!("Header checksum:   0x{:0>2X}, computed 0x{:0>2X} ({})", header.header_checksum, self.computed_header_checksum, pass_fail(header.header_checksum_valid));
+        println!("Global checksum:   0x{:0>4X}, computed 0x{:0>4X} ({})", header.global_checksum, self.computed_global_checksum, pass_fail(self.global_checksum_valid()));
+    }
+}
+
+fn pass_fail(ok: bool) -> &'static str
+{
+    match ok
+    {
+        true => "OK",
+        false => "FAILED"
+    }
+}
+
+fn cgb_name(cgb_support: CgbSupport) -> &'static str
+{
+    match cgb_support
+    {
+        CgbSupport::None => "None",
+        CgbSupport::Enhanced => "Enhanced",
+        CgbSupport::Only => "CGB only"
+    }
+}
+
+fn destination_name(destination: Destination) -> &'static str
+{
+    match destination
+    {
+        Destination::Japan => "Japan",
+        Destination::Overseas => "Overseas"
+    }
+}
+
+/// `info [--json] <rom>`: prints a ROM's header and checks it, without running anything. Header problems are
+/// reported rather than treated as errors, since finding them is the point.
+pub fn run(args: impl Iterator<Item = String>)
+{
+    let mut json = false;
+    let mut rom = None;
+
+    for arg in args
+    {
+        match arg.as_str()
+        {
+            "--json" => json = true,
+            _ if !arg.starts_with('-') && rom.is_none() => rom = Some(arg),
+            _ => crate::usage_error(format!("Unexpected argument \"{}\".", arg))
+        }
+    }
+
+    let path = match rom
+    {
+        Some(path) => path,
+        None => crate::usage_error("No ROM given.".to_string())
+    };
+
+    let info = cartridge::read_rom_file(Path::new(&path))
+        .map_err(cartridge::CartridgeError::from)
+        .and_then(| data | RomInfo::inspect(&data));
+
+    match (info, json)
+    {
+        (Ok(info), true) => println!("{}", serde_json::to_string_pretty(&info.to_json(&path)).unwrap()),
+        (Ok(info), false) => info.print(&path),
+        (Err(err), true) => {
+            // Still JSON, so whatever is parsing the output doesn't choke on it
+            println!("{}", serde_json::to_string_pretty(&json!({ "path": path, "error": err.to_string() })).unwrap());
+            std::process::exit(1);
+        },
+        (Err(err), false) => {
+            println!("Failed to read ROM at \"{}\". (Technical error: {})", path, err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,620 @@
+#![allow(dead_code)]
+
+/// Waveforms for the four square duty cycles: 12.5%, 25%, 50% and 75%
+const DUTY_TABLE: [u8; 4] = [0b0000_0001, 0b1000_0001, 0b1000_0111, 0b0111_1110];
+
+/// Noise channel divisors, picked by the low 3 bits of NR43
+const NOISE_DIVISORS: [u32; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/// Silences a channel after a number of frame sequencer length clocks
+#[derive(Clone, Copy)]
+pub struct LengthCounter
+{
+    pub enabled: bool,
+    counter: u16,
+    max: u16
+}
+
+impl LengthCounter
+{
+    fn new(max: u16) -> Self
+    {
+        LengthCounter { enabled: false, counter: 0, max }
+    }
+
+    /// Loads the counter from the length bits of NRx1
+    fn load(&mut self, length: u8)
+    {
+        self.counter = self.max - length as u16;
+    }
+
+    fn trigger(&mut self)
+    {
+        if self.counter == 0
+        {
+            self.counter = self.max;
+        }
+    }
+
+    /// Clocked at 256Hz. Returns true when the counter runs out and the channel should be disabled.
+    fn clock(&mut self) -> bool
+    {
+        if !self.enabled || self.counter == 0
+        {
+            return false;
+        }
+
+        self.counter -= 1;
+
+        self.counter == 0
+    }
+}
+
+/// Volume envelope shared by the square and noise channels, configured by NRx2
+#[derive(Clone, Copy, Default)]
+pub struct Envelope
+{
+    register: u8,
+    volume: u8,
+    timer: u8
+}
+
+impl Envelope
+{
+    /// The channel's DAC is on as long as any of the upper 5 bits of NRx2 are set
+    fn dac_enabled(&self) -> bool
+    {
+        self.register & 0xF8 != 0
+    }
+
+    fn period(&self) -> u8
+    {
+        self.register & 0x07
+    }
+
+    fn trigger(&mut self)
+    {
+        self.volume = self.register >> 4;
+        self.timer = self.period();
+    }
+
+    /// Clocked at 64Hz
+    fn clock(&mut self)
+    {
+        if self.period() == 0
+        {
+            return;
+        }
+
+        self.timer = self.timer.saturating_sub(1);
+
+        if self.timer != 0
+        {
+            return;
+        }
+
+        self.timer = self.period();
+
+        match self.register & 0x08
+        {
+            0 if self.volume > 0 => self.volume -= 1,
+            0x08 if self.volume < 15 => self.volume += 1,
+            _ => { }
+        }
+    }
+}
+
+/// Frequency sweep of channel 1, configured by NR10
+#[derive(Clone, Copy, Default)]
+pub struct Sweep
+{
+    register: u8,
+    enabled: bool,
+    shadow: u16,
+    timer: u8
+}
+
+impl Sweep
+{
+    fn period(&self) -> u8
+    {
+        self.register >> 4 & 0x07
+    }
+
+    fn shift(&self) -> u8
+    {
+        self.register & 0x07
+    }
+
+    /// Next frequency, or None if it overflows past 2047
+    fn next_frequency(&self) -> Option<u16>
+    {
+        let delta = self.shadow >> self.shift();
+
+        let frequency = match self.register & 0x08
+        {
+            0 => self.shadow + delta,
+            _ => self.shadow - delta
+        };
+
+        match frequency
+        {
+            0..=2047 => Some(frequency),
+            _ => None
+        }
+    }
+
+    /// A period of 0 is treated as 8 by the sweep timer
+    fn reload_timer(&mut self)
+    {
+        self.timer = match self.period()
+        {
+            0 => 8,
+            period => period
+        };
+    }
+}
+
+/// Square wave channels 1 and 2. Only channel 1 has a sweep unit.
+pub struct Square
+{
+    pub enabled: bool,
+    duty: u8,
+    duty_position: u8,
+    frequency: u16,
+    timer: i32,
+    pub length: LengthCounter,
+    envelope: Envelope,
+    sweep: Option<Sweep>
+}
+
+impl Square
+{
+    pub fn new(has_sweep: bool) -> Self
+    {
+        Square
+        {
+            enabled: false,
+            duty: 0,
+            duty_position: 0,
+            frequency: 0,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default(),
+            sweep: match has_sweep
+            {
+                true => Some(Sweep::default()),
+                false => None
+            }
+        }
+    }
+
+    fn period(&self) -> i32
+    {
+        (2048 - self.frequency as i32) * 4
+    }
+
+    pub fn dac_enabled(&self) -> bool
+    {
+        self.envelope.dac_enabled()
+    }
+
+    /// Current output level, 0-15
+    pub fn output(&self) -> u8
+    {
+        match self.enabled && (DUTY_TABLE[self.duty as usize] >> (7 - self.duty_position)) & 0x01 != 0
+        {
+            true => self.envelope.volume,
+            false => 0
+        }
+    }
+
+    /// Advances the frequency timer by a number of T-cycles
+    pub fn tick(&mut self, cycles: u32)
+    {
+        self.timer -= cycles as i32;
+
+        while self.timer <= 0
+        {
+            self.timer += self.period();
+            self.duty_position = (self.duty_position + 1) & 0x07;
+        }
+    }
+
+    pub fn clock_length(&mut self)
+    {
+        if self.length.clock()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self)
+    {
+        self.envelope.clock();
+    }
+
+    /// Clocked at 128Hz
+    pub fn clock_sweep(&mut self)
+    {
+        let Some(mut sweep) = self.sweep else
+        {
+            return;
+        };
+
+        sweep.timer = sweep.timer.saturating_sub(1);
+
+        if sweep.timer == 0
+        {
+            sweep.reload_timer();
+
+            if sweep.enabled && sweep.period() != 0
+            {
+                match sweep.next_frequency()
+                {
+                    Some(frequency) if sweep.shift() != 0 => {
+                        sweep.shadow = frequency;
+                        self.frequency = frequency;
+
+                        // The new frequency is checked for overflow again straight away, but not written back
+                        if sweep.next_frequency().is_none()
+                        {
+                            self.enabled = false;
+                        }
+                    },
+                    Some(_) => { },
+                    None => self.enabled = false
+                }
+            }
+        }
+
+        self.sweep = Some(sweep);
+    }
+
+    fn trigger(&mut self)
+    {
+        self.enabled = self.dac_enabled();
+        self.timer = self.period();
+        self.length.trigger();
+        self.envelope.trigger();
+
+        if let Some(sweep) = &mut self.sweep
+        {
+            sweep.shadow = self.frequency;
+            sweep.reload_timer();
+            sweep.enabled = sweep.period() != 0 || sweep.shift() != 0;
+
+            if sweep.shift() != 0 && sweep.next_frequency().is_none()
+            {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Reads NRx0-NRx4. Write-only bits read back as 1.
+    pub fn read(&self, register: u8) -> u8
+    {
+        match register
+        {
+            0 => match self.sweep
+            {
+                Some(sweep) => sweep.register | 0x80,
+                None => 0xFF
+            },
+            1 => (self.duty << 6) | 0x3F,
+            2 => self.envelope.register,
+            3 => 0xFF,
+            4 => ((self.length.enabled as u8) << 6) | 0xBF,
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, register: u8, data: u8)
+    {
+        match register
+        {
+            0 => {
+                if let Some(sweep) = &mut self.sweep
+                {
+                    sweep.register = data & 0x7F;
+                }
+            },
+            1 => {
+                self.duty = data >> 6;
+                self.length.load(data & 0x3F);
+            },
+            2 => {
+                self.envelope.register = data;
+
+                if !self.dac_enabled()
+                {
+                    self.enabled = false;
+                }
+            },
+            3 => self.frequency = (self.frequency & 0x0700) | data as u16,
+            4 => {
+                self.frequency = (self.frequency & 0x00FF) | (((data & 0x07) as u16) << 8);
+                self.length.enabled = data & 0x40 != 0;
+
+                if data & 0x80 != 0
+                {
+                    self.trigger();
+                }
+            },
+            _ => { }
+        }
+    }
+}
+
+/// Wave channel 3. Plays back 32 4-bit samples from wave RAM ($FF30-$FF3F).
+pub struct Wave
+{
+    pub enabled: bool,
+    dac_enabled: bool,
+    volume_code: u8,
+    frequency: u16,
+    timer: i32,
+    position: u8,
+    pub length: LengthCounter,
+    pub ram: [u8; 16]
+}
+
+impl Default for Wave
+{
+    fn default() -> Self
+    {
+        Wave::new()
+    }
+}
+
+impl Wave
+{
+    pub fn new() -> Self
+    {
+        Wave
+        {
+            enabled: false,
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            length: LengthCounter::new(256),
+            ram: [0; 16]
+        }
+    }
+
+    fn period(&self) -> i32
+    {
+        (2048 - self.frequency as i32) * 2
+    }
+
+    pub fn dac_enabled(&self) -> bool
+    {
+        self.dac_enabled
+    }
+
+    pub fn output(&self) -> u8
+    {
+        if !self.enabled
+        {
+            return 0;
+        }
+
+        let byte = self.ram[self.position as usize / 2];
+        let sample = match self.position & 0x01
+        {
+            0 => byte >> 4,
+            _ => byte & 0x0F
+        };
+
+        match self.volume_code
+        {
+            0 => 0,
+            1 => sample,
+            2 => sample >> 1,
+            _ => sample >> 2
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32)
+    {
+        self.timer -= cycles as i32;
+
+        while self.timer <= 0
+        {
+            self.timer += self.period();
+            self.position = (self.position + 1) & 0x1F;
+        }
+    }
+
+    pub fn clock_length(&mut self)
+    {
+        if self.length.clock()
+        {
+            self.enabled = false;
+        }
+    }
+
+    fn trigger(&mut self)
+    {
+        self.enabled = self.dac_enabled;
+        self.timer = self.period();
+        self.position = 0;
+        self.length.trigger();
+    }
+
+    pub fn read(&self, register: u8) -> u8
+    {
+        match register
+        {
+            0 => ((self.dac_enabled as u8) << 7) | 0x7F,
+            1 => 0xFF,
+            2 => (self.volume_code << 5) | 0x9F,
+            3 => 0xFF,
+            4 => ((self.length.enabled as u8) << 6) | 0xBF,
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, register: u8, data: u8)
+    {
+        match register
+        {
+            0 => {
+                self.dac_enabled = data & 0x80 != 0;
+
+                if !self.dac_enabled
+                {
+                    self.enabled = false;
+                }
+            },
+            1 => self.length.load(data),
+            2 => self.volume_code = (data >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x0700) | data as u16,
+            4 => {
+                self.frequency = (self.frequency & 0x00FF) | (((data & 0x07) as u16) << 8);
+                self.length.enabled = data & 0x40 != 0;
+
+                if data & 0x80 != 0
+                {
+                    self.trigger();
+                }
+            },
+            _ => { }
+        }
+    }
+}
+
+/// Noise channel 4. A linear feedback shift register, either 15 bits wide or shortened to 7 bits for a more tonal sound.
+pub struct Noise
+{
+    pub enabled: bool,
+    register: u8,
+    lfsr: u16,
+    timer: i32,
+    pub length: LengthCounter,
+    envelope: Envelope
+}
+
+impl Default for Noise
+{
+    fn default() -> Self
+    {
+        Noise::new()
+    }
+}
+
+impl Noise
+{
+    pub fn new() -> Self
+    {
+        Noise
+        {
+            enabled: false,
+            register: 0,
+            lfsr: 0x7FFF,
+            timer: 0,
+            length: LengthCounter::new(64),
+            envelope: Envelope::default()
+        }
+    }
+
+    fn period(&self) -> i32
+    {
+        (NOISE_DIVISORS[(self.register & 0x07) as usize] << (self.register >> 4)) as i32
+    }
+
+    pub fn dac_enabled(&self) -> bool
+    {
+        self.envelope.dac_enabled()
+    }
+
+    pub fn output(&self) -> u8
+    {
+        match self.enabled && self.lfsr & 0x01 == 0
+        {
+            true => self.envelope.volume,
+            false => 0
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u32)
+    {
+        self.timer -= cycles as i32;
+
+        while self.timer <= 0
+        {
+            self.timer += self.period();
+
+            let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 0x01;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+
+            // 7-bit mode also feeds back into bit 6
+            if self.register & 0x08 != 0
+            {
+                self.lfsr = (self.lfsr & !0x40) | (feedback << 6);
+            }
+        }
+    }
+
+    pub fn clock_length(&mut self)
+    {
+        if self.length.clock()
+        {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self)
+    {
+        self.envelope.clock();
+    }
+
+    fn trigger(&mut self)
+    {
+        self.enabled = self.dac_enabled();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+        self.length.trigger();
+        self.envelope.trigger();
+    }
+
+    pub fn read(&self, register: u8) -> u8
+    {
+        match register
+        {
+            1 => 0xFF,
+            2 => self.envelope.register,
+            3 => self.register,
+            4 => ((self.length.enabled as u8) << 6) | 0xBF,
+            _ => 0xFF
+        }
+    }
+
+    pub fn write(&mut self, register: u8, data: u8)
+    {
+        match register
+        {
+            1 => self.length.load(data & 0x3F),
+            2 => {
+                self.envelope.register = data;
+
+                if !self.dac_enabled()
+                {
+                    self.enabled = false;
+                }
+            },
+            3 => self.register = data,
+            4 => {
+                self.length.enabled = data & 0x40 != 0;
+
+                if data & 0x80 != 0
+                {
+                    self.trigger();
+                }
+            },
+            _ => { }
+        }
+    }
+}
@@ -0,0 +1,423 @@
+#![allow(dead_code)]
+
+mod channels;
+pub mod resampler;
+pub mod sink;
+pub mod wav;
+
+use crate::component::bus::MemoryMapped;
+use channels::{Noise, Square, Wave};
+use resampler::Resampler;
+
+/// CPU clock rate in T-cycles per second
+pub const CPU_CLOCK: u32 = 4_194_304;
+
+/// Sample rate used until the host asks for another one
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+/// T-cycles between frame sequencer steps (512Hz)
+const FRAME_SEQUENCER_PERIOD: u32 = 8192;
+
+/// The channels are stepped, and the output mixed, once per M-cycle
+const OUTPUT_CLOCK: u32 = CPU_CLOCK / 4;
+
+/// Audio processing unit. Runs the four sound channels and mixes them down into interleaved stereo `f32` samples
+/// (left, right), resampled to the host sample rate.
+///
+/// | Range           | Registers                          |
+/// |-----------------|------------------------------------|
+/// | `$FF10-$FF14`   | NR10-NR14, square 1 with sweep     |
+/// | `$FF16-$FF19`   | NR21-NR24, square 2                |
+/// | `$FF1A-$FF1E`   | NR30-NR34, wave                    |
+/// | `$FF20-$FF23`   | NR41-NR44, noise                   |
+/// | `$FF24-$FF26`   | NR50 volume, NR51 panning, NR52    |
+/// | `$FF30-$FF3F`   | Wave RAM                           |
+pub struct Apu
+{
+    square1: Square,
+    square2: Square,
+    wave: Wave,
+    noise: Noise,
+
+    /// NR50, master volume for each side
+    nr50: u8,
+    /// NR51, which channels go to which side
+    nr51: u8,
+    /// NR52 bit 7
+    powered: bool,
+
+    frame_step: u8,
+    frame_timer: u32,
+
+    resampler: Resampler,
+    /// M-cycles since the resampler's frame started
+    output_clock: u32,
+    samples: Vec<f32>
+}
+
+impl Apu
+{
+    pub fn new(sample_rate: u32) -> Self
+    {
+        Apu
+        {
+            square1: Square::new(true),
+            square2: Square::new(false),
+            wave: Wave::new(),
+            noise: Noise::new(),
+            nr50: 0x77,
+            nr51: 0xF3,
+            powered: true,
+            frame_step: 0,
+            frame_timer: FRAME_SEQUENCER_PERIOD,
+            resampler: Resampler::new(OUTPUT_CLOCK, sample_rate),
+            output_clock: 0,
+            samples: Vec::new()
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.resampler.sample_rate()
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32)
+    {
+        self.resampler.set_sample_rate(sample_rate);
+    }
+
+    /// Dynamic rate control, see `Resampler::set_rate_adjust`
+    pub fn set_rate_adjust(&mut self, rate_adjust: f64)
+    {
+        self.resampler.set_rate_adjust(rate_adjust);
+    }
+
+    /// Takes the samples generated since the last call, interleaved left then right
+    pub fn take_samples(&mut self) -> Vec<f32>
+    {
+        self.resampler.end_frame(self.output_clock, &mut self.samples);
+        self.output_clock = 0;
+
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Advances the APU by a number of T-cycles
+    pub fn tick(&mut self, cycles: u32)
+    {
+        for _ in 0..cycles / 4
+        {
+            self.step();
+        }
+    }
+
+    /// Advances by one M-cycle
+    fn step(&mut self)
+    {
+        if self.powered
+        {
+            self.square1.tick(4);
+            self.square2.tick(4);
+            self.wave.tick(4);
+            self.noise.tick(4);
+
+            self.frame_timer -= 4;
+
+            if self.frame_timer == 0
+            {
+                self.frame_timer = FRAME_SEQUENCER_PERIOD;
+                self.clock_frame_sequencer();
+            }
+        }
+
+        let (left, right) = self.mix();
+        self.resampler.add(self.output_clock, left, right);
+        self.output_clock += 1;
+
+        // Keep the resampler's frame short even if nobody is taking samples
+        if self.output_clock == OUTPUT_CLOCK
+        {
+            self.resampler.end_frame(self.output_clock, &mut self.samples);
+            self.output_clock = 0;
+        }
+    }
+
+    /// Steps 0, 2, 4 and 6 clock length (256Hz), steps 2 and 6 clock sweep (128Hz), step 7 clocks envelopes (64Hz)
+    fn clock_frame_sequencer(&mut self)
+    {
+        match self.frame_step
+        {
+            0 | 4 => self.clock_lengths(),
+            2 | 6 => {
+                self.clock_lengths();
+                self.square1.clock_sweep();
+            },
+            7 => {
+                self.square1.clock_envelope();
+                self.square2.clock_envelope();
+                self.noise.clock_envelope();
+            },
+            _ => { }
+        }
+
+        self.frame_step = (self.frame_step + 1) & 0x07;
+    }
+
+    fn clock_lengths(&mut self)
+    {
+        self.square1.clock_length();
+        self.square2.clock_length();
+        self.wave.clock_length();
+        self.noise.clock_length();
+    }
+
+    /// Each channel's DAC turns its 0-15 output into -1.0..1.0, or 0 while the DAC is off
+    fn dac(enabled: bool, output: u8) -> f32
+    {
+        match enabled
+        {
+            true => output as f32 / 7.5 - 1.0,
+            false => 0.0
+        }
+    }
+
+    /// Mixes the channels into a left and right sample according to NR51 and NR50
+    fn mix(&self) -> (f32, f32)
+    {
+        if !self.powered
+        {
+            return (0.0, 0.0);
+        }
+
+        let outputs = [
+            Apu::dac(self.square1.dac_enabled(), self.square1.output()),
+            Apu::dac(self.square2.dac_enabled(), self.square2.output()),
+            Apu::dac(self.wave.dac_enabled(), self.wave.output()),
+            Apu::dac(self.noise.dac_enabled(), self.noise.output())
+        ];
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+
+        for (channel, output) in outputs.iter().enumerate()
+        {
+            if self.nr51 & (0x10 << channel) != 0
+            {
+                left += output;
+            }
+
+            if self.nr51 & (0x01 << channel) != 0
+            {
+                right += output;
+            }
+        }
+
+        let left_volume = ((self.nr50 >> 4) & 0x07) as f32 + 1.0;
+        let right_volume = (self.nr50 & 0x07) as f32 + 1.0;
+
+        // 4 channels at up to 8x volume, scaled back down to -1.0..1.0
+        (left * left_volume / 32.0, right * right_volume / 32.0)
+    }
+
+    /// Powering off clears every register except wave RAM and the length counters
+    fn power_off(&mut self)
+    {
+        for address in 0xFF10..=0xFF25
+        {
+            match address
+            {
+                0xFF11 | 0xFF16 | 0xFF1B | 0xFF20 => { },
+                _ => self.write(address, 0x00)
+            }
+        }
+
+        self.square1.enabled = false;
+        self.square2.enabled = false;
+        self.wave.enabled = false;
+        self.noise.enabled = false;
+        self.powered = false;
+    }
+}
+
+impl MemoryMapped for Apu
+{
+    fn read(&self, address: u16) -> u8
+    {
+        match address
+        {
+            0xFF10..=0xFF14 => self.square1.read((address - 0xFF10) as u8),
+            0xFF15..=0xFF19 => self.square2.read((address - 0xFF15) as u8),
+            0xFF1A..=0xFF1E => self.wave.read((address - 0xFF1A) as u8),
+            0xFF1F..=0xFF23 => self.noise.read((address - 0xFF1F) as u8),
+            0xFF24 => self.nr50,
+            0xFF25 => self.nr51,
+            0xFF26 => {
+                let status = (self.square1.enabled as u8)
+                    | (self.square2.enabled as u8) << 1
+                    | (self.wave.enabled as u8) << 2
+                    | (self.noise.enabled as u8) << 3;
+
+                ((self.powered as u8) << 7) | 0x70 | status
+            },
+            0xFF30..=0xFF3F => self.wave.ram[(address - 0xFF30) as usize],
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8)
+    {
+        // While powered off only NR52 and wave RAM can be written, plus the length counters on DMG
+        let data = match (self.powered, address)
+        {
+            (true, _) | (false, 0xFF1B | 0xFF26 | 0xFF30..=0xFF3F) => data,
+            (false, 0xFF11 | 0xFF16 | 0xFF20) => data & 0x3F,
+            _ => return
+        };
+
+        match address
+        {
+            0xFF10..=0xFF14 => self.square1.write((address - 0xFF10) as u8, data),
+            0xFF15..=0xFF19 => self.square2.write((address - 0xFF15) as u8, data),
+            0xFF1A..=0xFF1E => self.wave.write((address - 0xFF1A) as u8, data),
+            0xFF1F..=0xFF23 => self.noise.write((address - 0xFF1F) as u8, data),
+            0xFF24 => self.nr50 = data,
+            0xFF25 => self.nr51 = data,
+            0xFF26 => match (self.powered, data & 0x80 != 0)
+            {
+                (true, false) => self.power_off(),
+                (false, true) => {
+                    self.powered = true;
+                    self.frame_step = 0;
+                    self.frame_timer = FRAME_SEQUENCER_PERIOD;
+                },
+                _ => { }
+            },
+            0xFF30..=0xFF3F => self.wave.ram[(address - 0xFF30) as usize] = data,
+            _ => { }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Whether a channel (0-3) is on according to NR52
+    fn channel_on(apu: &Apu, channel: u8) -> bool
+    {
+        apu.read(0xFF26) & (1 << channel) != 0
+    }
+
+    /// Runs the frame sequencer through a number of steps, starting from step 0
+    fn run_frame_steps(apu: &mut Apu, steps: u32)
+    {
+        apu.tick(FRAME_SEQUENCER_PERIOD * steps);
+    }
+
+    #[test]
+    fn sweep_overflow_on_trigger_disables_channel_1()
+    {
+        let mut apu = Apu::new(DEFAULT_SAMPLE_RATE);
+
+        // Sweep up by 1/2 from 2047 overflows straight away
+        apu.write(0xFF10, 0x11);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF13, 0xFF);
+        apu.write(0xFF14, 0x87);
+
+        assert!(!channel_on(&apu, 0));
+    }
+
+    #[test]
+    fn sweep_overflow_on_clock_disables_channel_1()
+    {
+        let mut apu = Apu::new(DEFAULT_SAMPLE_RATE);
+
+        // 1536 + 1536/4 = 1920 fits, but the check after writing it back (1920 + 480) overflows
+        apu.write(0xFF10, 0x12);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF13, 0x00);
+        apu.write(0xFF14, 0x86);
+        assert!(channel_on(&apu, 0));
+
+        // Sweep is clocked on step 2
+        run_frame_steps(&mut apu, 2);
+        assert!(channel_on(&apu, 0));
+
+        run_frame_steps(&mut apu, 1);
+        assert!(!channel_on(&apu, 0));
+    }
+
+    #[test]
+    fn length_counter_disables_channel()
+    {
+        let mut apu = Apu::new(DEFAULT_SAMPLE_RATE);
+
+        // Length 63 leaves one clock, and the length counter is clocked on step 0
+        apu.write(0xFF16, 0x3F);
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF19, 0xC0);
+        assert!(channel_on(&apu, 1));
+
+        run_frame_steps(&mut apu, 1);
+        assert!(!channel_on(&apu, 1));
+    }
+
+    #[test]
+    fn length_counter_only_counts_when_enabled()
+    {
+        let mut apu = Apu::new(DEFAULT_SAMPLE_RATE);
+
+        apu.write(0xFF16, 0x3F);
+        apu.write(0xFF17, 0xF0);
+        apu.write(0xFF19, 0x80);
+
+        run_frame_steps(&mut apu, 8);
+        assert!(channel_on(&apu, 1));
+    }
+
+    #[test]
+    fn dac_off_disables_channel()
+    {
+        let mut apu = Apu::new(DEFAULT_SAMPLE_RATE);
+
+        apu.write(0xFF21, 0xF0);
+        apu.write(0xFF23, 0x80);
+        assert!(channel_on(&apu, 3));
+
+        // Volume 0 and envelope down turns the DAC off
+        apu.write(0xFF21, 0x00);
+        assert!(!channel_on(&apu, 3));
+
+        // Triggering with the DAC off doesn't turn the channel on
+        apu.write(0xFF23, 0x80);
+        assert!(!channel_on(&apu, 3));
+    }
+
+    /// Highest output of square 2 over one whole waveform, at the fastest frequency
+    fn square2_peak(apu: &mut Apu) -> u8
+    {
+        (0..8).map(| _ | {
+            apu.tick(4);
+            apu.square2.output()
+        }).max().unwrap()
+    }
+
+    #[test]
+    fn envelope_fades_to_silence()
+    {
+        let mut apu = Apu::new(DEFAULT_SAMPLE_RATE);
+
+        // Square 2 at volume 1, stepping down every envelope clock
+        apu.write(0xFF16, 0x80);
+        apu.write(0xFF17, 0x11);
+        apu.write(0xFF18, 0xFF);
+        apu.write(0xFF19, 0x87);
+        assert_eq!(square2_peak(&mut apu), 1);
+
+        // Envelopes are clocked on step 7. The DAC stays on at volume 0, so the channel does too.
+        run_frame_steps(&mut apu, 8);
+        assert_eq!(square2_peak(&mut apu), 0);
+        assert!(channel_on(&apu, 1));
+    }
+}
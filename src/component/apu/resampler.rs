@@ -0,0 +1,295 @@
+#![allow(dead_code)]
+
+use std::f64::consts::PI;
+
+use crate::component::apu::CPU_CLOCK;
+
+/// Sub-sample positions the step kernel is precomputed at
+const PHASES: usize = 32;
+
+/// Output samples each step is spread across
+const KERNEL_WIDTH: usize = 16;
+
+/// Cutoff as a fraction of the output sample rate, a little under Nyquist to leave room for the filter's roll-off
+const CUTOFF: f64 = 0.45;
+
+/// DMG output capacitor charge factor per T-cycle
+const CAPACITOR_CHARGE: f64 = 0.999958;
+
+/// Converts the APU's output, which only ever changes in steps, into samples at the host rate.
+///
+/// Instead of point-sampling the output (which aliases badly, the channels run at up to 1MHz), each change in level
+/// is added to the output as a band-limited step: a windowed sinc impulse, placed at the step's exact sub-sample
+/// position, that gets integrated back into a step when samples are read. Work is proportional to how often the output
+/// changes, not to the clock rate.
+pub struct Resampler
+{
+    clock_rate: u32,
+    sample_rate: u32,
+    /// Multiplies the sample rate, for dynamic rate control
+    rate_adjust: f64,
+    /// Output samples per input clock
+    ratio: f64,
+    /// Output position of clock 0 of the current frame, relative to the start of `deltas`
+    offset: f64,
+    kernel: Vec<[f32; KERNEL_WIDTH]>,
+    /// Band-limited steps waiting to be integrated, per side
+    deltas: [Vec<f32>; 2],
+    /// Last input level per side
+    levels: [f32; 2],
+    /// Running sum of the deltas per side, which is the band-limited output level
+    integrators: [f32; 2],
+    high_pass: [HighPass; 2]
+}
+
+impl Resampler
+{
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self
+    {
+        let mut resampler = Resampler
+        {
+            clock_rate,
+            sample_rate,
+            rate_adjust: 1.0,
+            ratio: 0.0,
+            offset: 0.0,
+            kernel: Resampler::build_kernel(),
+            deltas: [Vec::new(), Vec::new()],
+            levels: [0.0; 2],
+            integrators: [0.0; 2],
+            high_pass: [HighPass::new(sample_rate), HighPass::new(sample_rate)]
+        };
+
+        resampler.update_ratio();
+
+        resampler
+    }
+
+    /// One windowed sinc impulse per phase, each normalised so a step of 1.0 integrates to exactly 1.0
+    fn build_kernel() -> Vec<[f32; KERNEL_WIDTH]>
+    {
+        let half = (KERNEL_WIDTH / 2) as f64;
+
+        (0..PHASES).map(| phase | {
+            let fraction = phase as f64 / PHASES as f64;
+            let mut taps = [0.0f64; KERNEL_WIDTH];
+
+            for (k, tap) in taps.iter_mut().enumerate()
+            {
+                let t = k as f64 - half + 1.0 - fraction;
+
+                let sinc = match t.abs() < f64::EPSILON
+                {
+                    true => 1.0,
+                    false => (2.0 * PI * CUTOFF * t).sin() / (2.0 * PI * CUTOFF * t)
+                };
+
+                // Blackman window
+                let window = 0.42 + 0.5 * (PI * t / half).cos() + 0.08 * (2.0 * PI * t / half).cos();
+
+                *tap = match t.abs() < half
+                {
+                    true => sinc * window,
+                    false => 0.0
+                };
+            }
+
+            let sum: f64 = taps.iter().sum();
+            taps.map(| tap | (tap / sum) as f32)
+        }).collect()
+    }
+
+    fn update_ratio(&mut self)
+    {
+        self.ratio = self.sample_rate as f64 * self.rate_adjust / self.clock_rate as f64;
+    }
+
+    pub fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32)
+    {
+        self.sample_rate = sample_rate;
+        self.high_pass = [HighPass::new(sample_rate), HighPass::new(sample_rate)];
+        self.update_ratio();
+    }
+
+    /// Nudges the output rate, e.g. 1.005 makes 0.5% more samples per emulated second. Frontends use this to keep
+    /// their audio queue from draining or piling up when the emulator isn't running at exactly the host's rate.
+    pub fn set_rate_adjust(&mut self, rate_adjust: f64)
+    {
+        self.rate_adjust = rate_adjust;
+        self.update_ratio();
+    }
+
+    /// Sets the input level at a clock in the current frame. Clocks must not go backwards within a frame.
+    pub fn add(&mut self, clock: u32, left: f32, right: f32)
+    {
+        for (side, level) in [left, right].into_iter().enumerate()
+        {
+            let delta = level - self.levels[side];
+
+            if delta != 0.0
+            {
+                self.levels[side] = level;
+                self.add_delta(side, clock, delta);
+            }
+        }
+    }
+
+    fn add_delta(&mut self, side: usize, clock: u32, delta: f32)
+    {
+        let position = self.offset + clock as f64 * self.ratio;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64) as usize;
+
+        let deltas = &mut self.deltas[side];
+
+        if deltas.len() < index + KERNEL_WIDTH
+        {
+            deltas.resize(index + KERNEL_WIDTH, 0.0);
+        }
+
+        for (k, tap) in self.kernel[phase].iter().enumerate()
+        {
+            deltas[index + k] += delta * tap;
+        }
+    }
+
+    /// Ends the current frame after a number of clocks, and appends every sample that is now complete to `out`,
+    /// interleaved left then right. The next frame starts at clock 0.
+    pub fn end_frame(&mut self, clocks: u32, out: &mut Vec<f32>)
+    {
+        let end = self.offset + clocks as f64 * self.ratio;
+        let count = end as usize;
+
+        for deltas in &mut self.deltas
+        {
+            if deltas.len() < count + KERNEL_WIDTH
+            {
+                deltas.resize(count + KERNEL_WIDTH, 0.0);
+            }
+        }
+
+        out.reserve(count * 2);
+
+        for n in 0..count
+        {
+            for side in 0..2
+            {
+                self.integrators[side] += self.deltas[side][n];
+                out.push(self.high_pass[side].filter(self.integrators[side]));
+            }
+        }
+
+        for deltas in &mut self.deltas
+        {
+            deltas.drain(..count);
+        }
+
+        self.offset = end - count as f64;
+    }
+}
+
+/// DC-blocking high-pass filter, modelling the capacitor on the Gameboy's audio output that centres the signal on 0
+#[derive(Clone, Copy)]
+struct HighPass
+{
+    charge: f32,
+    capacitor: f32
+}
+
+impl HighPass
+{
+    fn new(sample_rate: u32) -> Self
+    {
+        HighPass
+        {
+            charge: CAPACITOR_CHARGE.powf(CPU_CLOCK as f64 / sample_rate as f64) as f32,
+            capacitor: 0.0
+        }
+    }
+
+    fn filter(&mut self, input: f32) -> f32
+    {
+        let output = input - self.capacitor;
+        self.capacitor = input - output * self.charge;
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use crate::component::apu::Apu;
+
+    /// M-cycles per frame, the chunk size the APU usually ends frames at
+    const FRAME_CLOCKS: u32 = 17_556;
+
+    const CLOCK_RATE: u32 = CPU_CLOCK / 4;
+
+    /// Runs a number of frames with a constant input level, and returns the stereo output
+    fn run(resampler: &mut Resampler, frames: u32, level: f32) -> Vec<f32>
+    {
+        let mut out = Vec::new();
+
+        for _ in 0..frames
+        {
+            resampler.add(0, level, level);
+            resampler.end_frame(FRAME_CLOCKS, &mut out);
+        }
+
+        out
+    }
+
+    #[test]
+    fn produces_samples_at_output_rate()
+    {
+        let mut resampler = Resampler::new(CLOCK_RATE, 48_000);
+
+        let out = run(&mut resampler, 600, 0.0);
+
+        // The fractional sample left over at the end of each frame carries into the next, so nothing is lost
+        let expected = (600 * FRAME_CLOCKS) as u64 * 48_000 / CLOCK_RATE as u64;
+        assert_eq!(out.len() as u64, expected * 2);
+    }
+
+    #[test]
+    fn rate_adjust_scales_sample_count()
+    {
+        let mut resampler = Resampler::new(CLOCK_RATE, 44_100);
+        resampler.set_rate_adjust(1.01);
+
+        let out = run(&mut resampler, 60, 0.0);
+
+        let expected = (60 * FRAME_CLOCKS) as f64 * 44_100.0 * 1.01 / CLOCK_RATE as f64;
+        assert!((out.len() as f64 / 2.0 - expected).abs() <= 1.0);
+    }
+
+    #[test]
+    fn blocks_dc()
+    {
+        let mut resampler = Resampler::new(CLOCK_RATE, 48_000);
+
+        // A step from 0 to 1 comes through, then drains away as the capacitor charges
+        let out = run(&mut resampler, 60, 1.0);
+
+        let peak = out.iter().cloned().fold(0.0, f32::max);
+        assert!(peak > 0.9);
+        assert!(out[out.len() - 2..].iter().all(| sample | sample.abs() < 0.01));
+    }
+
+    #[test]
+    fn apu_makes_a_second_of_samples_from_a_second_of_cycles()
+    {
+        let mut apu = Apu::new(48_000);
+
+        apu.tick(CPU_CLOCK);
+
+        assert_eq!(apu.take_samples().len(), 48_000 * 2);
+    }
+}
@@ -0,0 +1,18 @@
+#![allow(dead_code)]
+
+/// Where the emulator sends audio. Implemented by frontends (an audio device queue, a WAV writer, ...).
+pub trait AudioSink
+{
+    /// Rate the sink wants samples at, in Hz
+    fn sample_rate(&self) -> u32;
+
+    /// Receives a batch of interleaved stereo samples (left, right) in -1.0..1.0. Called once per frame.
+    fn push_samples(&mut self, samples: &[f32]);
+
+    /// Dynamic rate control. Returns a factor to multiply the sample rate by for the next frame, so a sink tied to
+    /// a real device can ask for a few more samples when its queue runs low, and fewer when it fills up.
+    fn rate_adjust(&mut self) -> f64
+    {
+        1.0
+    }
+}
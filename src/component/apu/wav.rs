@@ -0,0 +1,200 @@
+#![allow(dead_code)]
+
+use std::{fs::File, io::{self, BufWriter, Seek, SeekFrom, Write}, path::Path};
+
+use crate::component::apu::sink::AudioSink;
+
+/// Size of the RIFF/WAVE header written before the sample data
+const HEADER_LEN: u32 = 44;
+
+/// Largest amount of sample data the RIFF size field can describe
+const MAX_DATA_LEN: u32 = u32::MAX - (HEADER_LEN - 8);
+
+/// Seconds of audio between header updates
+const HEADER_INTERVAL_SECS: u32 = 1;
+
+/// Records audio to a 16-bit stereo PCM WAV file.
+///
+/// The header's size fields are patched when the writer is finished or dropped, and about once a second while
+/// recording, so a file cut short by a crash loses at most the last second.
+pub struct WavWriter
+{
+    file: BufWriter<File>,
+    sample_rate: u32,
+    /// Bytes of sample data written so far
+    data_len: u32,
+    /// Value of `data_len` when the header was last written
+    header_len: u32,
+    /// Set after a write fails or the file is full, so the error is only reported once
+    failed: bool
+}
+
+impl WavWriter
+{
+    pub fn create(path: &Path, sample_rate: u32) -> io::Result<Self>
+    {
+        let mut writer = WavWriter
+        {
+            file: BufWriter::new(File::create(path)?),
+            sample_rate,
+            data_len: 0,
+            header_len: 0,
+            failed: false
+        };
+
+        writer.write_header()?;
+
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()>
+    {
+        let channels: u16 = 2;
+        let bits_per_sample: u16 = 16;
+        let block_align = channels * bits_per_sample / 8;
+
+        self.file.seek(SeekFrom::Start(0))?;
+
+        self.file.write_all(b"RIFF")?;
+        self.file.write_all(&(HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        self.file.write_all(b"WAVE")?;
+
+        self.file.write_all(b"fmt ")?;
+        self.file.write_all(&16u32.to_le_bytes())?;
+        self.file.write_all(&1u16.to_le_bytes())?; // PCM
+        self.file.write_all(&channels.to_le_bytes())?;
+        self.file.write_all(&self.sample_rate.to_le_bytes())?;
+        self.file.write_all(&(self.sample_rate * block_align as u32).to_le_bytes())?;
+        self.file.write_all(&block_align.to_le_bytes())?;
+        self.file.write_all(&bits_per_sample.to_le_bytes())?;
+
+        self.file.write_all(b"data")?;
+        self.file.write_all(&self.data_len.to_le_bytes())?;
+
+        self.file.seek(SeekFrom::End(0))?;
+
+        self.header_len = self.data_len;
+
+        Ok(())
+    }
+
+    fn write_samples(&mut self, samples: &[f32]) -> io::Result<()>
+    {
+        let data_len = u32::try_from(samples.len() * 2).ok()
+            .and_then(| len | self.data_len.checked_add(len))
+            .filter(| len | *len <= MAX_DATA_LEN)
+            .ok_or_else(|| io::Error::other("the file has reached the 4GiB WAV size limit"))?;
+
+        for sample in samples
+        {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.file.write_all(&value.to_le_bytes())?;
+        }
+
+        self.data_len = data_len;
+
+        let interval = self.sample_rate.saturating_mul(4).saturating_mul(HEADER_INTERVAL_SECS);
+
+        match self.data_len - self.header_len >= interval
+        {
+            true => self.finish(),
+            false => Ok(())
+        }
+    }
+
+    /// Patches the header with the final sizes and flushes everything to disk. Also called on drop.
+    pub fn finish(&mut self) -> io::Result<()>
+    {
+        self.write_header()?;
+        self.file.flush()
+    }
+}
+
+impl Drop for WavWriter
+{
+    fn drop(&mut self)
+    {
+        // A failed writer has already patched what it could
+        if self.failed
+        {
+            return;
+        }
+
+        if let Err(err) = self.finish()
+        {
+            println!("Failed to finish audio capture. The file may be unreadable. (Technical error: {})", err);
+        }
+    }
+}
+
+impl AudioSink for WavWriter
+{
+    fn sample_rate(&self) -> u32
+    {
+        self.sample_rate
+    }
+
+    fn push_samples(&mut self, samples: &[f32])
+    {
+        if self.failed
+        {
+            return;
+        }
+
+        if let Err(err) = self.write_samples(samples)
+        {
+            println!("Failed to write audio capture. The recording will be cut short. (Technical error: {})", err);
+            self.failed = true;
+
+            // Keep what was recorded before the failure playable
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn temp_wav(name: &str) -> std::path::PathBuf
+    {
+        std::env::temp_dir().join(format!("argentous-oxide-{}-{}.wav", name, std::process::id()))
+    }
+
+    fn data_size(path: &Path) -> u32
+    {
+        let bytes = std::fs::read(path).unwrap();
+        u32::from_le_bytes(bytes[40..44].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_is_patched_on_drop()
+    {
+        let path = temp_wav("drop");
+        let mut writer = WavWriter::create(&path, 48_000).unwrap();
+        writer.push_samples(&[0.5; 64]);
+        drop(writer);
+
+        assert_eq!(data_size(&path), 128);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), HEADER_LEN as u64 + 128);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stops_at_the_size_limit()
+    {
+        let path = temp_wav("limit");
+        let mut writer = WavWriter::create(&path, 48_000).unwrap();
+        writer.data_len = MAX_DATA_LEN - 2;
+
+        assert!(writer.write_samples(&[0.0; 2]).is_err());
+        assert!(writer.write_samples(&[0.0]).is_ok());
+        assert_eq!(writer.data_len, MAX_DATA_LEN);
+
+        writer.failed = true;
+        drop(writer);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+#![allow(dead_code)]
+
+use crate::component::{apu::{Apu, DEFAULT_SAMPLE_RATE}, cartridge::Cartridge, interrupt::InterruptController, joypad::Joypad, ppu::Ppu, timer::Timer};
+
+/// Anything that answers to reads and writes on the address bus
+pub trait MemoryMapped
+{
+    fn read(&self, address: u16) -> u8;
+    fn write(&mut self, address: u16, data: u8);
+}
+
+/// The Gameboy's memory map. Routes each access to the component that owns the address.
+///
+/// | Range           | Region                        |
+/// |-----------------|-------------------------------|
+/// | `$0000-$7FFF`   | Cartridge ROM                 |
+/// | `$8000-$9FFF`   | VRAM                          |
+/// | `$A000-$BFFF`   | Cartridge RAM                 |
+/// | `$C000-$DFFF`   | WRAM                          |
+/// | `$E000-$FDFF`   | Echo RAM (mirror of `$C000`)  |
+/// | `$FE00-$FE9F`   | OAM                           |
+/// | `$FEA0-$FEFF`   | Unusable                      |
+/// | `$FF00-$FF7F`   | IO registers                  |
+/// | `$FF80-$FFFE`   | HRAM                          |
+/// | `$FFFF`         | IE                            |
+///
+/// While a boot ROM is mapped it covers `$0000-$00FF` (and `$0200-$08FF` for a CGB boot ROM), until it's unmapped by a
+/// write to `$FF50`.
+pub struct Bus
+{
+    pub cartridge: Option<Cartridge>,
+    /// Mapped over the start of cartridge ROM until the boot ROM unmaps itself
+    pub boot_rom: Option<Vec<u8>>,
+    pub wram: [u8; 0x2000],
+    pub io: [u8; 0x80],
+    pub hram: [u8; 0x7F],
+    /// IF ($FF0F) and IE ($FFFF)
+    pub interrupts: InterruptController,
+    pub timer: Timer,
+    /// P1/JOYP ($FF00)
+    pub joypad: Joypad,
+    /// Owns VRAM, OAM and the LCD registers
+    pub ppu: Ppu,
+    /// Sound registers and wave RAM
+    pub apu: Apu
+}
+
+impl Default for Bus
+{
+    fn default() -> Self
+    {
+        Bus::new()
+    }
+}
+
+impl Bus
+{
+    pub fn new() -> Self
+    {
+        Bus
+        {
+            cartridge: None,
+            boot_rom: None,
+            wram: [0; 0x2000],
+            io: [0; 0x80],
+            hram: [0; 0x7F],
+            interrupts: InterruptController::new(),
+            timer: Timer::new(),
+            joypad: Joypad::new(),
+            ppu: Ppu::new(),
+            apu: Apu::new(DEFAULT_SAMPLE_RATE)
+        }
+    }
+
+    /// Advances the components on the bus by a number of CPU cycles
+    pub fn tick(&mut self, cycles: u32)
+    {
+        for _ in 0..cycles / 4
+        {
+            self.timer.step(&mut self.interrupts);
+        }
+
+        self.ppu.tick(cycles, &mut self.interrupts);
+        self.apu.tick(cycles);
+
+        if let Some(cart) = &mut self.cartridge
+        {
+            cart.tick(cycles);
+        }
+    }
+
+    /// The boot ROM byte at an address, if the boot ROM is mapped and covers it. `$0100-$01FF` always comes from the
+    /// cartridge, so the boot ROM can read the header.
+    fn boot_rom_byte(&self, address: u16) -> Option<u8>
+    {
+        match address
+        {
+            0x0100..=0x01FF => None,
+            _ => self.boot_rom.as_ref()?.get(address as usize).copied()
+        }
+    }
+
+    /// OAM DMA ($FF46). Copies 160 bytes from `$XX00` into OAM. The copy is done all at once rather than over 160
+    /// M-cycles, which games can't tell apart since they wait it out in HRAM anyway.
+    fn oam_dma(&mut self, page: u8)
+    {
+        let source = (page as u16) << 8;
+
+        for i in 0..0xA0
+        {
+            self.ppu.oam[i as usize] = self.read(source + i);
+        }
+    }
+}
+
+impl MemoryMapped for Bus
+{
+    fn read(&self, address: u16) -> u8
+    {
+        if let Some(data) = self.boot_rom_byte(address)
+        {
+            return data;
+        }
+
+        match address
+        {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => match &self.cartridge
+            {
+                Some(cart) => cart.read(address),
+                None => 0xFF // Open bus
+            },
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.ppu.read(address),
+            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize],
+            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize],
+            0xFEA0..=0xFEFF => 0x00,
+            0xFF00 => self.joypad.read(),
+            0xFF04..=0xFF07 => self.timer.read(address),
+            0xFF0F | 0xFFFF => self.interrupts.read(address),
+            0xFF10..=0xFF3F => self.apu.read(address),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.read(address),
+            0xFF01..=0xFF7F => self.io[(address - 0xFF00) as usize],
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize],
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            0x0000..=0x7FFF | 0xA000..=0xBFFF => {
+                if let Some(cart) = &mut self.cartridge
+                {
+                    cart.write(address, data);
+                }
+            },
+            0x8000..=0x9FFF | 0xFE00..=0xFE9F => self.ppu.write(address, data),
+            0xC000..=0xDFFF => self.wram[(address - 0xC000) as usize] = data,
+            0xE000..=0xFDFF => self.wram[(address - 0xE000) as usize] = data,
+            0xFEA0..=0xFEFF => { }, // Writes to the unusable region are ignored
+            0xFF00 => self.joypad.write_select(data, &mut self.interrupts),
+            0xFF04..=0xFF07 => self.timer.write(address, data),
+            0xFF0F | 0xFFFF => self.interrupts.write(address, data),
+            0xFF10..=0xFF3F => self.apu.write(address, data),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.write(address, data),
+            0xFF46 => {
+                self.io[0x46] = data;
+                self.oam_dma(data);
+            },
+            0xFF50 => {
+                // Any write unmaps the boot ROM for good
+                self.io[0x50] = data;
+                self.boot_rom = None;
+            },
+            0xFF01..=0xFF7F => self.io[(address - 0xFF00) as usize] = data,
+            0xFF80..=0xFFFE => self.hram[(address - 0xFF80) as usize] = data,
+        }
+    }
+}
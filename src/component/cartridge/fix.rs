@@ -0,0 +1,102 @@
+#![allow(dead_code)]
+
+use crate::component::cartridge::{header, CartridgeError, HEADER_END, MAX_ROM_SIZE, NINTENDO_LOGO};
+
+/// Smallest ROM a cartridge can have: two 16KiB banks
+const MIN_ROM_SIZE: usize = 0x8000;
+
+/// Fill for padding, the same as unprogrammed flash and open bus
+const PAD_BYTE: u8 = 0xFF;
+
+/// What `fix_size` changed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SizeFix
+{
+    pub old_len: usize,
+    pub new_len: usize,
+    /// Header byte 0x148
+    pub old_code: u8,
+    pub new_code: u8
+}
+
+/// Checks a ROM is long enough to have a header to fix. The fixes below assume it is.
+///
+/// They are meant to be run in the order they're listed in, like rgbfix does, since the header checksum covers 0x148
+/// and the global checksum covers everything.
+pub fn check_len(rom: &[u8]) -> Result<(), CartridgeError>
+{
+    match rom.len() < HEADER_END
+    {
+        true => Err(CartridgeError::TooShort(rom.len())),
+        false => Ok(())
+    }
+}
+
+/// Overwrites the Nintendo logo at $0104-$0133. Returns whether it was wrong.
+pub fn fix_logo(rom: &mut [u8]) -> bool
+{
+    if rom[0x104..=0x133] == NINTENDO_LOGO
+    {
+        return false;
+    }
+
+    rom[0x104..=0x133].copy_from_slice(&NINTENDO_LOGO);
+
+    true
+}
+
+/// Pads the ROM with 0xFF up to the next size the header can declare (a power of two from 32KiB), and sets 0x148 to
+/// match. Fails if the ROM is too big for any size the header can declare.
+pub fn fix_size(rom: &mut Vec<u8>) -> Result<Option<SizeFix>, CartridgeError>
+{
+    let old_len = rom.len();
+    let new_len = old_len.next_power_of_two().max(MIN_ROM_SIZE);
+
+    if new_len > MAX_ROM_SIZE
+    {
+        return Err(CartridgeError::TooLarge(old_len));
+    }
+
+    let old_code = rom[0x148];
+    let new_code = (new_len / MIN_ROM_SIZE).trailing_zeros() as u8;
+
+    if old_len == new_len && old_code == new_code
+    {
+        return Ok(None);
+    }
+
+    rom.resize(new_len, PAD_BYTE);
+    rom[0x148] = new_code;
+
+    Ok(Some(SizeFix { old_len, new_len, old_code, new_code }))
+}
+
+/// Recomputes the header checksum at 0x14D. Returns the old and new values if it was wrong.
+pub fn fix_header_checksum(rom: &mut [u8]) -> Option<(u8, u8)>
+{
+    let old = rom[0x14D];
+    let new = header::header_checksum(rom);
+
+    rom[0x14D] = new;
+
+    match old == new
+    {
+        true => None,
+        false => Some((old, new))
+    }
+}
+
+/// Recomputes the global checksum at 0x14E-0x14F. Returns the old and new values if it was wrong.
+pub fn fix_global_checksum(rom: &mut [u8]) -> Option<(u16, u16)>
+{
+    let old = ((rom[0x14E] as u16) << 8) | rom[0x14F] as u16;
+    let new = header::global_checksum(rom);
+
+    rom[0x14E..=0x14F].copy_from_slice(&new.to_be_bytes());
+
+    match old == new
+    {
+        true => None,
+        false => Some((old, new))
+    }
+}
@@ -0,0 +1,556 @@
+#![allow(dead_code)]
+
+use std::num::Wrapping;
+
+use crate::component::cartridge::{CartridgeError, HEADER_END, NINTENDO_LOGO};
+
+/// The cartridge header at `$0100-$014F`, parsed once when the ROM is loaded.
+///
+/// | Range           | Field                                         |
+/// |-----------------|-----------------------------------------------|
+/// | `$0100-$0103`   | Entry point                                   |
+/// | `$0104-$0133`   | Nintendo logo                                 |
+/// | `$0134-$0143`   | Title                                         |
+/// | `$013F-$0142`   | Manufacturer code (newer cartridges)          |
+/// | `$0143`         | CGB flag                                      |
+/// | `$0144-$0145`   | New licensee code                             |
+/// | `$0146`         | SGB flag                                      |
+/// | `$0147`         | Cartridge type                                |
+/// | `$0148`         | ROM size                                      |
+/// | `$0149`         | RAM size                                      |
+/// | `$014A`         | Destination code                              |
+/// | `$014B`         | Old licensee code                             |
+/// | `$014C`         | Mask ROM version                              |
+/// | `$014D`         | Header checksum                               |
+/// | `$014E-$014F`   | Global checksum (big endian)                  |
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CartridgeHeader
+{
+    /// Usually a NOP and a jump to the real start of the game
+    pub entry_point: [u8; 4],
+    pub logo_valid: bool,
+    pub title: String,
+    /// Only present on newer cartridges, which shortened the title to make room for it
+    pub manufacturer_code: Option<String>,
+    pub cgb_support: CgbSupport,
+    /// Only used when the old licensee code is 0x33
+    pub new_licensee_code: Option<String>,
+    pub old_licensee_code: u8,
+    pub sgb_supported: bool,
+    pub cartridge_type: CartridgeType,
+    pub rom_size: RomSize,
+    pub ram_size: RamSize,
+    pub destination: Destination,
+    pub version: u8,
+    /// As stored at 0x14D
+    pub header_checksum: u8,
+    /// Whether the stored header checksum matches the header, which the boot ROM requires
+    pub header_checksum_valid: bool,
+    /// As stored at 0x14E. Nothing checks this on hardware.
+    pub global_checksum: u16
+}
+
+impl CartridgeHeader
+{
+    pub fn parse(rom: &[u8]) -> Result<Self, CartridgeError>
+    {
+        if rom.len() < HEADER_END
+        {
+            return Err(CartridgeError::TooShort(rom.len()));
+        }
+
+        let cgb_support = CgbSupport::from_byte(rom[0x143]);
+
+        // The manufacturer code took the last 4 characters of the title. There's no flag for it, but it only appears
+        // on CGB-aware cartridges and is always uppercase.
+        let manufacturer_code = match cgb_support
+        {
+            CgbSupport::None => None,
+            _ => ascii_code(&rom[0x13F..=0x142])
+        };
+
+        let title_end = match (manufacturer_code.is_some(), cgb_support)
+        {
+            (true, _) => 0x13E,
+            (false, CgbSupport::None) => 0x143,
+            (false, _) => 0x142
+        };
+
+        let title = rom[0x134..=title_end]
+            .iter()
+            .take_while(| b | **b != 0x00)
+            .map(| b | *b as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+
+        let old_licensee_code = rom[0x14B];
+
+        let new_licensee_code = match old_licensee_code
+        {
+            0x33 => Some(rom[0x144..=0x145].iter().map(| b | *b as char).collect()),
+            _ => None
+        };
+
+        Ok(CartridgeHeader
+        {
+            entry_point: rom[0x100..=0x103].try_into().unwrap(),
+            logo_valid: rom[0x104..=0x133] == NINTENDO_LOGO,
+            title,
+            manufacturer_code,
+            cgb_support,
+            new_licensee_code,
+            old_licensee_code,
+            sgb_supported: rom[0x146] == 0x03,
+            cartridge_type: CartridgeType::from_byte(rom[0x147]),
+            rom_size: RomSize::from_byte(rom[0x148]),
+            ram_size: RamSize::from_byte(rom[0x149]),
+            destination: Destination::from_byte(rom[0x14A]),
+            version: rom[0x14C],
+            header_checksum: rom[0x14D],
+            header_checksum_valid: rom[0x14D] == header_checksum(rom),
+            global_checksum: ((rom[0x14E] as u16) << 8) | rom[0x14F] as u16
+        })
+    }
+}
+
+/// Header checksum over $0134-$014C, as checked by the boot ROM
+pub fn header_checksum(rom: &[u8]) -> u8
+{
+    rom[0x134..=0x14C].iter().fold(0u8, | x, b | x.wrapping_sub(*b).wrapping_sub(1))
+}
+
+/// Global checksum: the sum of every byte in the ROM, all banks included, except the two checksum bytes themselves.
+/// Nothing checks this on hardware.
+pub fn global_checksum(rom: &[u8]) -> u16
+{
+    let mut x: Wrapping<u16> = Wrapping(0);
+
+    for (i, e) in rom.iter().enumerate()
+    {
+        if i == 0x14E || i == 0x14F
+        {
+            continue;
+        }
+
+        x += Wrapping(*e as u16);
+    }
+
+    x.0
+}
+
+/// Reads a code made of uppercase letters and digits, or None if the bytes aren't one
+fn ascii_code(bytes: &[u8]) -> Option<String>
+{
+    match bytes.iter().all(| b | b.is_ascii_uppercase() || b.is_ascii_digit())
+    {
+        true => Some(bytes.iter().map(| b | *b as char).collect()),
+        false => None
+    }
+}
+
+/// CGB flag (0x143)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgbSupport
+{
+    /// A DMG game
+    None,
+    /// Uses CGB features when available, but still runs on a DMG
+    Enhanced,
+    /// Refuses to run on a DMG
+    Only
+}
+
+impl CgbSupport
+{
+    pub fn from_byte(byte: u8) -> Self
+    {
+        match byte
+        {
+            0xC0 => CgbSupport::Only,
+            0x80..=0xFF => CgbSupport::Enhanced,
+            _ => CgbSupport::None
+        }
+    }
+}
+
+/// Cartridge type (0x147): which memory bank controller the cartridge has, and what else is on the board.
+///
+/// Capabilities follow the hardware, which for a few types is more than the name in the header lists (HuC3 and TAMA5
+/// carts have a clock and battery, the Pocket Camera has its camera and a battery).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CartridgeType
+{
+    RomOnly,
+    Mbc1,
+    Mbc1Ram,
+    Mbc1RamBattery,
+    Mbc2,
+    Mbc2Battery,
+    RomRam,
+    RomRamBattery,
+    Mmm01,
+    Mmm01Ram,
+    Mmm01RamBattery,
+    Mbc3TimerBattery,
+    Mbc3TimerRamBattery,
+    Mbc3,
+    Mbc3Ram,
+    Mbc3RamBattery,
+    Mbc5,
+    Mbc5Ram,
+    Mbc5RamBattery,
+    Mbc5Rumble,
+    Mbc5RumbleRam,
+    Mbc5RumbleRamBattery,
+    Mbc6,
+    Mbc7SensorRumbleRamBattery,
+    PocketCamera,
+    BandaiTama5,
+    HuC3,
+    HuC1RamBattery,
+    Unknown(u8)
+}
+
+impl CartridgeType
+{
+    /// Every documented type, in header byte order
+    pub const ALL: [CartridgeType; 28] = [
+        CartridgeType::RomOnly,
+        CartridgeType::Mbc1,
+        CartridgeType::Mbc1Ram,
+        CartridgeType::Mbc1RamBattery,
+        CartridgeType::Mbc2,
+        CartridgeType::Mbc2Battery,
+        CartridgeType::RomRam,
+        CartridgeType::RomRamBattery,
+        CartridgeType::Mmm01,
+        CartridgeType::Mmm01Ram,
+        CartridgeType::Mmm01RamBattery,
+        CartridgeType::Mbc3TimerBattery,
+        CartridgeType::Mbc3TimerRamBattery,
+        CartridgeType::Mbc3,
+        CartridgeType::Mbc3Ram,
+        CartridgeType::Mbc3RamBattery,
+        CartridgeType::Mbc5,
+        CartridgeType::Mbc5Ram,
+        CartridgeType::Mbc5RamBattery,
+        CartridgeType::Mbc5Rumble,
+        CartridgeType::Mbc5RumbleRam,
+        CartridgeType::Mbc5RumbleRamBattery,
+        CartridgeType::Mbc6,
+        CartridgeType::Mbc7SensorRumbleRamBattery,
+        CartridgeType::PocketCamera,
+        CartridgeType::BandaiTama5,
+        CartridgeType::HuC3,
+        CartridgeType::HuC1RamBattery
+    ];
+
+    pub fn from_byte(byte: u8) -> Self
+    {
+        CartridgeType::ALL
+            .into_iter()
+            .find(| cart_type | cart_type.byte() == byte)
+            .unwrap_or(CartridgeType::Unknown(byte))
+    }
+
+    pub fn byte(&self) -> u8
+    {
+        match self
+        {
+            CartridgeType::RomOnly => 0x00,
+            CartridgeType::Mbc1 => 0x01,
+            CartridgeType::Mbc1Ram => 0x02,
+            CartridgeType::Mbc1RamBattery => 0x03,
+            CartridgeType::Mbc2 => 0x05,
+            CartridgeType::Mbc2Battery => 0x06,
+            CartridgeType::RomRam => 0x08,
+            CartridgeType::RomRamBattery => 0x09,
+            CartridgeType::Mmm01 => 0x0B,
+            CartridgeType::Mmm01Ram => 0x0C,
+            CartridgeType::Mmm01RamBattery => 0x0D,
+            CartridgeType::Mbc3TimerBattery => 0x0F,
+            CartridgeType::Mbc3TimerRamBattery => 0x10,
+            CartridgeType::Mbc3 => 0x11,
+            CartridgeType::Mbc3Ram => 0x12,
+            CartridgeType::Mbc3RamBattery => 0x13,
+            CartridgeType::Mbc5 => 0x19,
+            CartridgeType::Mbc5Ram => 0x1A,
+            CartridgeType::Mbc5RamBattery => 0x1B,
+            CartridgeType::Mbc5Rumble => 0x1C,
+            CartridgeType::Mbc5RumbleRam => 0x1D,
+            CartridgeType::Mbc5RumbleRamBattery => 0x1E,
+            CartridgeType::Mbc6 => 0x20,
+            CartridgeType::Mbc7SensorRumbleRamBattery => 0x22,
+            CartridgeType::PocketCamera => 0xFC,
+            CartridgeType::BandaiTama5 => 0xFD,
+            CartridgeType::HuC3 => 0xFE,
+            CartridgeType::HuC1RamBattery => 0xFF,
+            CartridgeType::Unknown(byte) => *byte
+        }
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            CartridgeType::RomOnly => "ROM ONLY",
+            CartridgeType::Mbc1 => "MBC1",
+            CartridgeType::Mbc1Ram => "MBC1 + RAM",
+            CartridgeType::Mbc1RamBattery => "MBC1 + RAM + Battery",
+            CartridgeType::Mbc2 => "MBC2",
+            CartridgeType::Mbc2Battery => "MBC2 + Battery",
+            CartridgeType::RomRam => "ROM + RAM",
+            CartridgeType::RomRamBattery => "ROM + RAM + Battery",
+            CartridgeType::Mmm01 => "MMM01",
+            CartridgeType::Mmm01Ram => "MMM01 + RAM",
+            CartridgeType::Mmm01RamBattery => "MMM01 + RAM + Battery",
+            CartridgeType::Mbc3TimerBattery => "MBC3 + Battery + Timer",
+            CartridgeType::Mbc3TimerRamBattery => "MBC3 + RAM + Battery + Timer",
+            CartridgeType::Mbc3 => "MBC3",
+            CartridgeType::Mbc3Ram => "MBC3 + RAM",
+            CartridgeType::Mbc3RamBattery => "MBC3 + RAM + Battery",
+            CartridgeType::Mbc5 => "MBC5",
+            CartridgeType::Mbc5Ram => "MBC5 + RAM",
+            CartridgeType::Mbc5RamBattery => "MBC5 + RAM + Battery",
+            CartridgeType::Mbc5Rumble => "MBC5 + Rumble",
+            CartridgeType::Mbc5RumbleRam => "MBC5 + Rumble + RAM",
+            CartridgeType::Mbc5RumbleRamBattery => "MBC5 + Rumble + RAM + Battery",
+            CartridgeType::Mbc6 => "MBC6",
+            CartridgeType::Mbc7SensorRumbleRamBattery => "MBC7 + Sensor + Rumble + RAM + Battery",
+            CartridgeType::PocketCamera => "Pocket Camera",
+            CartridgeType::BandaiTama5 => "Bandai TAMA5",
+            CartridgeType::HuC3 => "HuC3",
+            CartridgeType::HuC1RamBattery => "HuC1 + RAM + Battery",
+            CartridgeType::Unknown(_) => "INVALID"
+        }
+    }
+
+    /// Whether this is a documented type. Being known doesn't mean it's emulated.
+    pub fn is_known(&self) -> bool
+    {
+        !matches!(self, CartridgeType::Unknown(_))
+    }
+
+    /// Most 16KiB ROM banks the controller can address
+    pub fn max_rom_banks(&self) -> u16
+    {
+        match self
+        {
+            CartridgeType::RomOnly | CartridgeType::RomRam | CartridgeType::RomRamBattery => 2,
+            CartridgeType::Mbc2 | CartridgeType::Mbc2Battery => 16,
+            CartridgeType::BandaiTama5 => 32,
+            CartridgeType::Mbc6 | CartridgeType::PocketCamera | CartridgeType::HuC1RamBattery => 64,
+            CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery
+            | CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery | CartridgeType::Mbc3 | CartridgeType::Mbc3Ram | CartridgeType::Mbc3RamBattery
+            | CartridgeType::Mbc7SensorRumbleRamBattery | CartridgeType::HuC3 => 128,
+            CartridgeType::Mmm01 | CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery
+            | CartridgeType::Mbc5 | CartridgeType::Mbc5Ram | CartridgeType::Mbc5RamBattery
+            | CartridgeType::Mbc5Rumble | CartridgeType::Mbc5RumbleRam | CartridgeType::Mbc5RumbleRamBattery => 512,
+            CartridgeType::Unknown(_) => 0
+        }
+    }
+
+    /// Most 8KiB RAM banks the controller can address, or 0 without RAM. RAM built into the controller (MBC2's 512
+    /// nibbles, MBC7's EEPROM, TAMA5's registers) counts as a single bank.
+    pub fn max_ram_banks(&self) -> u8
+    {
+        if !self.has_ram()
+        {
+            return 0;
+        }
+
+        match self
+        {
+            CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery
+            | CartridgeType::Mbc5Ram | CartridgeType::Mbc5RamBattery | CartridgeType::Mbc5RumbleRam | CartridgeType::Mbc5RumbleRamBattery
+            | CartridgeType::PocketCamera => 16,
+            CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery
+            | CartridgeType::Mbc3TimerRamBattery | CartridgeType::Mbc3Ram | CartridgeType::Mbc3RamBattery
+            | CartridgeType::Mbc6 | CartridgeType::HuC3 | CartridgeType::HuC1RamBattery => 4,
+            _ => 1
+        }
+    }
+
+    pub fn has_ram(&self) -> bool
+    {
+        matches!(self,
+            CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery
+            | CartridgeType::Mbc2 | CartridgeType::Mbc2Battery
+            | CartridgeType::RomRam | CartridgeType::RomRamBattery
+            | CartridgeType::Mmm01Ram | CartridgeType::Mmm01RamBattery
+            | CartridgeType::Mbc3TimerRamBattery | CartridgeType::Mbc3Ram | CartridgeType::Mbc3RamBattery
+            | CartridgeType::Mbc5Ram | CartridgeType::Mbc5RamBattery | CartridgeType::Mbc5RumbleRam | CartridgeType::Mbc5RumbleRamBattery
+            | CartridgeType::Mbc6 | CartridgeType::Mbc7SensorRumbleRamBattery
+            | CartridgeType::PocketCamera | CartridgeType::BandaiTama5 | CartridgeType::HuC3 | CartridgeType::HuC1RamBattery)
+    }
+
+    pub fn has_battery(&self) -> bool
+    {
+        matches!(self,
+            CartridgeType::Mbc1RamBattery | CartridgeType::Mbc2Battery | CartridgeType::RomRamBattery | CartridgeType::Mmm01RamBattery
+            | CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery | CartridgeType::Mbc3RamBattery
+            | CartridgeType::Mbc5RamBattery | CartridgeType::Mbc5RumbleRamBattery | CartridgeType::Mbc7SensorRumbleRamBattery
+            | CartridgeType::PocketCamera | CartridgeType::BandaiTama5 | CartridgeType::HuC3 | CartridgeType::HuC1RamBattery)
+    }
+
+    /// Real time clock
+    pub fn has_timer(&self) -> bool
+    {
+        matches!(self, CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery | CartridgeType::BandaiTama5 | CartridgeType::HuC3)
+    }
+
+    pub fn has_rumble(&self) -> bool
+    {
+        matches!(self, CartridgeType::Mbc5Rumble | CartridgeType::Mbc5RumbleRam | CartridgeType::Mbc5RumbleRamBattery | CartridgeType::Mbc7SensorRumbleRamBattery)
+    }
+
+    /// MBC7's accelerometer, or the Pocket Camera's image sensor
+    pub fn has_sensor(&self) -> bool
+    {
+        matches!(self, CartridgeType::Mbc7SensorRumbleRamBattery | CartridgeType::PocketCamera)
+    }
+}
+
+/// ROM size (0x148)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RomSize
+{
+    Kib32,
+    Kib64,
+    Kib128,
+    Kib256,
+    Kib512,
+    Mib1,
+    Mib2,
+    Mib4,
+    Mib8,
+    /// 72 banks. Unofficial, listed in some old documentation.
+    Mib1_1,
+    /// 80 banks. Unofficial.
+    Mib1_2,
+    /// 96 banks. Unofficial.
+    Mib1_5,
+    Unknown(u8)
+}
+
+impl RomSize
+{
+    const SIZES: [RomSize; 9] = [RomSize::Kib32, RomSize::Kib64, RomSize::Kib128, RomSize::Kib256, RomSize::Kib512, RomSize::Mib1, RomSize::Mib2, RomSize::Mib4, RomSize::Mib8];
+
+    pub fn from_byte(byte: u8) -> Self
+    {
+        match byte
+        {
+            0x00..=0x08 => RomSize::SIZES[byte as usize],
+            0x52 => RomSize::Mib1_1,
+            0x53 => RomSize::Mib1_2,
+            0x54 => RomSize::Mib1_5,
+            _ => RomSize::Unknown(byte)
+        }
+    }
+
+    pub fn byte(&self) -> u8
+    {
+        match self
+        {
+            RomSize::Mib1_1 => 0x52,
+            RomSize::Mib1_2 => 0x53,
+            RomSize::Mib1_5 => 0x54,
+            RomSize::Unknown(byte) => *byte,
+            size => RomSize::SIZES.iter().position(| s | s == size).unwrap() as u8
+        }
+    }
+
+    /// Size in bytes, or None if unknown
+    pub fn bytes(&self) -> Option<usize>
+    {
+        match self
+        {
+            RomSize::Mib1_1 => Some(72 * 16 * 1024),
+            RomSize::Mib1_2 => Some(80 * 16 * 1024),
+            RomSize::Mib1_5 => Some(96 * 16 * 1024),
+            RomSize::Unknown(_) => None,
+            size => Some((32 * 1024) << size.byte())
+        }
+    }
+}
+
+/// External RAM size (0x149)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RamSize
+{
+    None,
+    /// Unofficial, listed by some homebrew
+    Kib2,
+    Kib8,
+    Kib32,
+    Kib128,
+    Kib64,
+    Unknown(u8)
+}
+
+impl RamSize
+{
+    pub fn from_byte(byte: u8) -> Self
+    {
+        match byte
+        {
+            0x00 => RamSize::None,
+            0x01 => RamSize::Kib2,
+            0x02 => RamSize::Kib8,
+            0x03 => RamSize::Kib32,
+            0x04 => RamSize::Kib128,
+            0x05 => RamSize::Kib64,
+            _ => RamSize::Unknown(byte)
+        }
+    }
+
+    pub fn byte(&self) -> u8
+    {
+        match self
+        {
+            RamSize::None => 0x00,
+            RamSize::Kib2 => 0x01,
+            RamSize::Kib8 => 0x02,
+            RamSize::Kib32 => 0x03,
+            RamSize::Kib128 => 0x04,
+            RamSize::Kib64 => 0x05,
+            RamSize::Unknown(byte) => *byte
+        }
+    }
+
+    /// Size in bytes. Unknown sizes count as no RAM.
+    pub fn bytes(&self) -> usize
+    {
+        match self
+        {
+            RamSize::Kib2 => 2 * 1024,
+            RamSize::Kib8 => 8 * 1024,
+            RamSize::Kib32 => 32 * 1024,
+            RamSize::Kib128 => 128 * 1024,
+            RamSize::Kib64 => 64 * 1024,
+            RamSize::None | RamSize::Unknown(_) => 0
+        }
+    }
+}
+
+/// Destination code (0x14A)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Destination
+{
+    Japan,
+    Overseas
+}
+
+impl Destination
+{
+    pub fn from_byte(byte: u8) -> Self
+    {
+        match byte
+        {
+            0x00 => Destination::Japan,
+            _ => Destination::Overseas
+        }
+    }
+}
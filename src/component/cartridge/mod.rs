@@ -0,0 +1,367 @@
+#![allow(dead_code)]
+
+use std::{error::Error, fmt, fs::{self, read}, io::{self, Cursor, Read}, path::{Path, PathBuf}};
+
+use crate::{component::{bus::MemoryMapped, mbc::{self, Mbc}, rtc::{ClockSource, SystemClock}}};
+
+pub mod fix;
+pub mod header;
+
+use header::{CartridgeHeader, CartridgeType, RomSize};
+
+/// The logo every licensed cartridge carries at $0104-$0133, which the boot ROM checks before starting the game
+pub const NINTENDO_LOGO: [u8; 48] = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E];
+
+/// First bytes of a zip archive's local file header
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+
+/// Smallest file that can hold a cartridge header
+const HEADER_END: usize = 0x150;
+
+/// Biggest ROM header byte 0x148 can declare with a power of two size: 512 banks
+const MAX_ROM_SIZE: usize = 0x80_0000;
+
+/// Why a ROM couldn't be loaded
+#[derive(Debug)]
+pub enum CartridgeError
+{
+    /// The file (or archive) couldn't be read
+    Io(io::Error),
+    /// Too short to hold a cartridge header
+    TooShort(usize),
+    /// Bigger than the 8MiB the header at 0x148 can declare
+    TooLarge(usize),
+    /// The cartridge type at 0x147 is unknown, or isn't emulated
+    UnsupportedMbc(CartridgeType),
+    /// The ROM isn't the size its header at 0x148 declares. Only reported by `Cartridge::problems`.
+    SizeMismatch { header: u8, actual: usize },
+    /// The header checksum at 0x14D doesn't match the header, so the boot ROM would refuse it. Only reported by
+    /// `Cartridge::problems`.
+    BadHeaderChecksum { expected: u8, found: u8 },
+    /// Has neither the Nintendo logo nor a consistent header, so it's most likely not a ROM at all
+    NotARom
+}
+
+impl fmt::Display for CartridgeError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self
+        {
+            CartridgeError::Io(err) => write!(f, "{}", err),
+            CartridgeError::TooShort(len) => write!(f, "file is {}B, too short to hold a cartridge header", len),
+            CartridgeError::TooLarge(len) => write!(f, "ROM is {}B, bigger than the {}B a cartridge header can declare", len, MAX_ROM_SIZE),
+            CartridgeError::UnsupportedMbc(CartridgeType::Unknown(byte)) => write!(f, "unknown cartridge type 0x{:0>2X}", byte),
+            CartridgeError::UnsupportedMbc(cart_type) => write!(f, "cartridge type 0x{:0>2X} ({}) is not supported", cart_type.byte(), cart_type.name()),
+            CartridgeError::SizeMismatch { header, actual } => match RomSize::from_byte(*header).bytes()
+            {
+                Some(size) => write!(f, "ROM is {}B, but its header (0x148 = 0x{:0>2X}) declares {}B", actual, header, size),
+                None => write!(f, "ROM is {}B, but its header has an unknown ROM size (0x148 = 0x{:0>2X})", actual, header)
+            },
+            CartridgeError::BadHeaderChecksum { expected, found } => write!(f, "header checksum failed (expected: 0x{:0>2X}, given: 0x{:0>2X})", expected, found),
+            CartridgeError::NotARom => write!(f, "no Nintendo logo or valid cartridge header found")
+        }
+    }
+}
+
+impl Error for CartridgeError
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)>
+    {
+        match self
+        {
+            CartridgeError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for CartridgeError
+{
+    fn from(err: io::Error) -> Self
+    {
+        CartridgeError::Io(err)
+    }
+}
+
+pub struct Cartridge
+{
+    pub rom: Vec<u8>,
+    pub ram: Vec<u8>,
+
+    mbc: Box<dyn Mbc>,
+    header: CartridgeHeader,
+
+    /// Where battery-backed RAM is persisted, None if the cartridge has no battery
+    save_path: Option<PathBuf>,
+    /// Set when RAM has been written since the last save
+    ram_dirty: bool
+}
+
+impl Cartridge
+{
+    /// Loads a ROM file, or the first ROM in a zip archive. Battery-backed cartridges save next to the file.
+    pub fn read_rom(path: &str) -> Result<Self, CartridgeError>
+    {
+        println!("Reading from \"{}\"...", path);
+
+        let rom = read_rom_file(Path::new(path))?;
+        println!("Read ok. Bytes read: {}B | {}KiB", rom.len(), (rom.len() / 1024));
+
+        // Archives were already checked when the ROM was picked out of them. Files too short for a header are left
+        // for `from_bytes` to report.
+        if rom.len() >= HEADER_END && !looks_like_rom(&rom)
+        {
+            return Err(CartridgeError::NotARom);
+        }
+
+        let mut cart = Cartridge::from_bytes(rom)?;
+
+        if cart.has_battery()
+        {
+            cart.save_path = Some(Path::new(path).with_extension("sav"));
+        }
+
+        Ok(cart)
+    }
+
+    /// Builds a cartridge from a ROM image. Only a missing header or an unsupported controller stop it loading; a bad
+    /// checksum or size is left to the caller, through `problems`. The cartridge has no save file until one is set with
+    /// `set_save_path`.
+    pub fn from_bytes(rom: Vec<u8>) -> Result<Self, CartridgeError>
+    {
+        Cartridge::with_clock(rom, Box::new(SystemClock))
+    }
+
+    /// Same as `from_bytes`, but a real-time clock on the cartridge reads wall-clock time from the given source
+    pub fn with_clock(rom: Vec<u8>, clock: Box<dyn ClockSource>) -> Result<Self, CartridgeError>
+    {
+        let header = CartridgeHeader::parse(&rom)?;
+
+        let mbc = match mbc::mbc_with_clock(header.cartridge_type, clock)
+        {
+            Some(mbc) => mbc,
+            None => return Err(CartridgeError::UnsupportedMbc(header.cartridge_type))
+        };
+
+        let ram_size = header.ram_size.bytes().max(mbc.builtin_ram_size());
+
+        Ok(Cartridge {
+            ram: vec![0; ram_size],
+            rom,
+            mbc,
+            header,
+            save_path: None,
+            ram_dirty: false
+        })
+    }
+
+    pub fn header(&self) -> &CartridgeHeader
+    {
+        &self.header
+    }
+
+    /// Header problems that don't stop the cartridge running, but that real hardware or a strict loader would refuse
+    pub fn problems(&self) -> Vec<CartridgeError>
+    {
+        let mut problems = Vec::new();
+
+        if !self.header.header_checksum_valid
+        {
+            problems.push(CartridgeError::BadHeaderChecksum { expected: header::header_checksum(&self.rom), found: self.header.header_checksum });
+        }
+
+        if self.header.rom_size.bytes() != Some(self.rom.len())
+        {
+            problems.push(CartridgeError::SizeMismatch { header: self.header.rom_size.byte(), actual: self.rom.len() });
+        }
+
+        problems
+    }
+
+    pub fn has_battery(&self) -> bool
+    {
+        self.header.cartridge_type.has_battery()
+    }
+
+    /// Where battery-backed RAM is saved. Ignored if the cartridge has no battery.
+    pub fn set_save_path(&mut self, path: PathBuf)
+    {
+        if self.has_battery()
+        {
+            self.save_path = Some(path);
+        }
+    }
+
+    /// Keeps the save file in another directory instead of next to the ROM. Call before loading the save.
+    pub fn set_save_dir(&mut self, dir: &Path)
+    {
+        if let Some(path) = &self.save_path
+        {
+            self.save_path = path.file_name().map(| name | dir.join(name));
+        }
+    }
+
+    /// Whether RAM has changed since the last call to `write_save_file`
+    pub fn is_dirty(&self) -> bool
+    {
+        self.ram_dirty
+    }
+
+    /// Loads battery-backed RAM from the cartridge's .sav file, if it has a battery and the file exists
+    pub fn load_save_file(&mut self)
+    {
+        let path = match &self.save_path
+        {
+            Some(p) => p.clone(),
+            None => return
+        };
+
+        match read(&path)
+        {
+            Ok(data) => {
+                println!("Loaded save data from \"{}\" ({}B)", path.display(), data.len());
+                self.load_battery_save(&data);
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => { },
+            Err(err) => println!("Failed to read save data from \"{}\". Starting with blank RAM. (Technical error: {})", path.display(), err)
+        }
+    }
+
+    /// Writes battery-backed RAM to the cartridge's .sav file. The data is written to a temporary file first and then
+    /// renamed over the old save, so a crash part way through can't leave a truncated save behind.
+    pub fn write_save_file(&mut self) -> io::Result<()>
+    {
+        let path = match &self.save_path
+        {
+            Some(p) => p.clone(),
+            None => return Ok(())
+        };
+
+        let temp_path = path.with_extension("sav.tmp");
+
+        fs::write(&temp_path, self.battery_save())?;
+        fs::rename(&temp_path, &path)?;
+
+        self.ram_dirty = false;
+
+        Ok(())
+    }
+
+    pub fn tick(&mut self, cycles: u32)
+    {
+        self.mbc.tick(cycles);
+    }
+
+    /// Battery-backed state as stored in a save file: external RAM, followed by the RTC footer if there is a clock
+    pub fn battery_save(&self) -> Vec<u8>
+    {
+        let mut data = self.ram.clone();
+
+        if let Some(footer) = self.mbc.save_footer()
+        {
+            data.extend_from_slice(&footer);
+        }
+
+        data
+    }
+
+    /// Restores state written by `battery_save`. Anything past the end of RAM is treated as the RTC footer.
+    pub fn load_battery_save(&mut self, data: &[u8])
+    {
+        let ram_len = self.ram.len().min(data.len());
+
+        self.ram[..ram_len].copy_from_slice(&data[..ram_len]);
+        self.mbc.load_footer(&data[ram_len..]);
+    }
+}
+
+impl MemoryMapped for Cartridge
+{
+    fn read(&self, address: u16) -> u8
+    {
+        match address
+        {
+            0x0000..=0x7FFF => self.mbc.read_rom(&self.rom, address),
+            0xA000..=0xBFFF => self.mbc.read_ram(&self.ram, address),
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            0x0000..=0x7FFF => self.mbc.write_register(address, data),
+            // Writes dropped by disabled or missing RAM don't need saving
+            0xA000..=0xBFFF => self.ram_dirty |= self.mbc.write_ram(&mut self.ram, address, data),
+            _ => { }
+        }
+    }
+}
+
+/// Whether data has a Gameboy cartridge header, going by the Nintendo logo, or failing that the header checksum along
+/// with a known cartridge type and ROM size. A checksum alone matches one in 256 files. Extensions can't be trusted
+/// (.gb, .gbc, .sgb and .bin are all in use), so this is what picks the ROM out of an archive.
+pub fn looks_like_rom(data: &[u8]) -> bool
+{
+    if data.len() < HEADER_END
+    {
+        return false;
+    }
+
+    data[0x104..=0x133] == NINTENDO_LOGO
+        || (data[0x14D] == header::header_checksum(data)
+            && CartridgeType::from_byte(data[0x147]).is_known()
+            && RomSize::from_byte(data[0x148]).bytes().is_some())
+}
+
+/// Reads a ROM from a file without checking it. Zip archives are opened and the first ROM inside is used.
+pub fn read_rom_file(path: &Path) -> io::Result<Vec<u8>>
+{
+    extract_rom(read(path)?)
+}
+
+/// Takes the ROM out of file data: the first ROM inside if it's a zip archive, otherwise the data as it is
+pub fn extract_rom(data: Vec<u8>) -> io::Result<Vec<u8>>
+{
+    match is_archive(&data)
+    {
+        true => read_rom_from_zip(data),
+        false => Ok(data)
+    }
+}
+
+/// Whether file data is a zip archive rather than a bare ROM
+pub fn is_archive(data: &[u8]) -> bool
+{
+    data.starts_with(&ZIP_MAGIC)
+}
+
+/// Finds the first file in a zip archive that has a cartridge header
+fn read_rom_from_zip(data: Vec<u8>) -> io::Result<Vec<u8>>
+{
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))?;
+
+    for i in 0..archive.len()
+    {
+        let mut file = archive.by_index(i)?;
+
+        if !file.is_file() || file.size() > MAX_ROM_SIZE as u64
+        {
+            continue;
+        }
+
+        // The size in the archive can't be trusted either, so the read is capped rather than preallocated
+        let mut contents = Vec::new();
+        (&mut file).take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut contents)?;
+
+        if contents.len() <= MAX_ROM_SIZE && looks_like_rom(&contents)
+        {
+            println!("Found ROM \"{}\" in archive", file.name());
+            return Ok(contents);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "no Gameboy ROM found in archive"))
+}
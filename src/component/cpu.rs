@@ -1,12 +1,25 @@
 #![allow(dead_code)]
 
-use crate::{cpu::operations, lookups};
+use crate::{component::bus::{Bus, MemoryMapped}, cpu::{disassembler, opcodes, operations}};
 
 pub struct Cpu
 {
-    pub memory: [u8; 65536],
+    pub bus: Bus,
     pub registers: Registers,
     pub cache: Vec<u8>,
+    /// Interrupt master enable, toggled by EI/DI/RETI
+    pub ime: bool,
+    /// Instructions left before a pending EI takes effect. EI enables interrupts only after the instruction following it.
+    pub ime_delay: u8,
+    /// Set by HALT, cleared when any enabled interrupt is requested
+    pub halted: bool,
+    /// Set when HALT is executed with IME off and an interrupt already pending. The CPU fails to increment PC after
+    /// the next opcode fetch, so that byte is read twice.
+    pub halt_bug: bool,
+    /// Set by conditional instructions to pick between the taken and not-taken cycle costs
+    pub branch_taken: bool,
+    /// M-cycles the rest of the system has already been stepped through by memory accesses this step
+    pub ticked: u8
 }
 
 pub enum Flag
@@ -39,51 +52,101 @@ pub enum Register
     L
 }
 
+impl Default for Registers
+{
+    fn default() -> Self
+    {
+        Registers::new()
+    }
+}
+
 impl Registers
 {
     pub fn new() -> Self
     {
-        Registers 
-        { 
-            af: 0x0100, 
-            bc: 0x0014, 
-            de: 0x0000, 
-            hl: 0xC060, 
-            pc: 0x00FF, 
-            sp: 0xFFFE 
+        Registers
+        {
+            af: 0x0100,
+            bc: 0x0014,
+            de: 0x0000,
+            hl: 0xC060,
+            pc: 0x0100,
+            sp: 0xFFFE
         }
     }
 }
 
+impl Default for Cpu
+{
+    fn default() -> Self
+    {
+        Cpu::new()
+    }
+}
+
 impl Cpu
 {
     pub fn new() -> Self
     {
-        Cpu 
-        { 
-            memory: [0; 65536], 
+        Cpu
+        {
+            bus: Bus::new(),
             registers: Registers::new(),
-            cache: Vec::new()
+            cache: Vec::new(),
+            ime: false,
+            ime_delay: 0,
+            halted: false,
+            halt_bug: false,
+            branch_taken: true,
+            ticked: 0
         }
     }
 
-    pub fn execute(&mut self, opcode: u8) -> Option<u8>
+    /// Reads the opcode at PC and loads its operands into the cache, leaving PC on the next instruction
+    pub fn fetch(&mut self) -> u8
+    {
+        self.cache.clear();
+
+        let opcode = self.read_byte(self.registers.pc);
+
+        match self.halt_bug
+        {
+            true => self.halt_bug = false,
+            false => self.registers.pc = self.registers.pc.wrapping_add(1)
+        }
+
+        // Load bytes into cache
+        while self.cache.len() < (opcodes::instruction_len(opcode) - 1) as usize
+        {
+            let byte = self.read_byte(self.registers.pc);
+            self.cache.push(byte);
+            self.registers.pc = self.registers.pc.wrapping_add(1);
+        }
+
+        opcode
+    }
+
+    /// Executes a fetched instruction. Returns the number of M-cycles it took, or an exit code if execution can't
+    /// continue.
+    pub fn execute(&mut self, opcode: u8) -> Result<u8, u8>
     {
-        match opcode
+        self.branch_taken = true;
+
+        let result = match opcode
         {
             0x10 => {
-                return Some(0); // STOP opcode
+                Some(0) // STOP opcode
             },
             0xD3 | 0xDB | 0xDD | 0xE3 | 0xE4 | 0xEB | 0xEC | 0xED | 0xF4 | 0xFC | 0xFD => {
-                return Some(4); // Unknown opcode
+                Some(4) // Unknown opcode
             },
             _ => {
-                return match lookups::instruction_len(&opcode)
+                match opcodes::instruction_len(opcode)
                 {
                     1 => {
                         let inst = operations::inst_len1(self, opcode);
                         inst(self, opcode)
-                    }, 
+                    },
                     2 => {
                         let inst = operations::inst_len2(self, opcode, self.cache[0]);
                         inst(self, opcode, self.cache[0])
@@ -97,9 +160,94 @@ impl Cpu
                     }
                 }
             }
+        };
+
+        if let Some(code) = result
+        {
+            return Err(code);
+        }
+
+        if self.ime_delay > 0
+        {
+            self.ime_delay -= 1;
+
+            if self.ime_delay == 0
+            {
+                self.ime = true;
+            }
+        }
+
+        let entry = match opcode
+        {
+            0xCB => opcodes::lookup_prefixed(self.cache[0]),
+            _ => opcodes::lookup(opcode)
+        };
+
+        // The table is in T-cycles, and is always a multiple of 4
+        match self.branch_taken
+        {
+            true => Ok(entry.cycles[0] / 4),
+            false => Ok(entry.cycles[1] / 4)
         }
     }
 
+    /// Wakes the CPU from HALT if an enabled interrupt has been requested, and dispatches the highest priority one if
+    /// IME is set. Returns the number of M-cycles spent dispatching, or None if no interrupt was serviced.
+    pub fn handle_interrupts(&mut self) -> Option<u8>
+    {
+        if self.bus.interrupts.pending() == 0
+        {
+            return None;
+        }
+
+        // HALT ends as soon as an interrupt is pending, even if IME is off
+        self.halted = false;
+
+        if !self.ime
+        {
+            return None;
+        }
+
+        self.ime = false;
+        self.ime_delay = 0;
+
+        let vector = self.bus.interrupts.acknowledge().unwrap();
+
+        self.tick();
+        self.tick();
+        self.push_stack_u16(self.registers.pc);
+        self.registers.pc = vector;
+
+        // 2 wait states, 2 cycles to push PC, and 1 to jump
+        Some(5)
+    }
+
+    /// Steps the rest of the system through one M-cycle. Every memory access takes one, so that reads and writes land
+    /// on the cycle they happen on within the instruction, rather than all at its start.
+    fn tick(&mut self)
+    {
+        self.bus.tick(4);
+        self.ticked = self.ticked.saturating_add(1);
+    }
+
+    /// Returns and resets the M-cycles already stepped through this step, which the caller doesn't need to step again
+    pub fn take_ticked(&mut self) -> u8
+    {
+        std::mem::take(&mut self.ticked)
+    }
+
+    pub fn read_byte(&mut self, address: u16) -> u8
+    {
+        self.tick();
+        self.bus.read(address)
+    }
+
+    pub fn write_byte(&mut self, address: u16, data: u8)
+    {
+        self.tick();
+        self.bus.write(address, data);
+    }
+
     pub fn set_flag(&mut self, flag: Flag)
     {
         self.registers.af |= (0b0000_0001u8 << flag as u8) as u16;
@@ -107,7 +255,7 @@ impl Cpu
 
     pub fn clear_flag(&mut self, flag: Flag)
     {
-        self.registers.af &= !(0b0000_0001u8 << flag as u8) as u16;
+        self.registers.af &= !((0b0000_0001u8 << flag as u8) as u16);
     }
 
     pub fn get_flag(&self, flag: Flag) -> bool
@@ -117,26 +265,38 @@ impl Cpu
 
     pub fn push_stack(&mut self, data: u8)
     {
-        self.registers.sp -= 1;
+        self.registers.sp = self.registers.sp.wrapping_sub(1);
 
-        self.memory[self.registers.sp as usize] = data;
+        self.write_byte(self.registers.sp, data);
     }
 
     pub fn pop_stack(&mut self) -> u8
     {
-        let data = self.memory[self.registers.sp as usize];
+        let data = self.read_byte(self.registers.sp);
 
-        if self.registers.sp != 0xFFFF
-        {
-            self.registers.sp += 1;
-        }
+        self.registers.sp = self.registers.sp.wrapping_add(1);
 
         data
     }
 
-    pub fn load_inter_register(&mut self, target: Register, source: Register)
+    /// Pushes a 16-bit value, high byte first, so that it sits little-endian in memory
+    pub fn push_stack_u16(&mut self, data: u16)
     {
-        let value: u8 = match source
+        self.push_stack((data >> 8) as u8);
+        self.push_stack(data as u8);
+    }
+
+    pub fn pop_stack_u16(&mut self) -> u16
+    {
+        let low = self.pop_stack() as u16;
+        let high = self.pop_stack() as u16;
+
+        (high << 8) | low
+    }
+
+    pub fn get_register(&self, register: Register) -> u8
+    {
+        match register
         {
             Register::A => (self.registers.af >> 8) as u8,
             Register::F => self.registers.af as u8,
@@ -146,12 +306,15 @@ impl Cpu
             Register::E => self.registers.de as u8,
             Register::H => (self.registers.hl >> 8) as u8,
             Register::L => self.registers.hl as u8,
-        };
+        }
+    }
 
-        match target
+    pub fn set_register(&mut self, register: Register, value: u8)
+    {
+        match register
         {
             Register::A => self.registers.af = (self.registers.af & 0x00FF) | ((value as u16) << 8),
-            Register::F => self.registers.af = (self.registers.af & 0xFF00) | (value as u16),
+            Register::F => self.registers.af = (self.registers.af & 0xFF00) | (value & 0xF0) as u16, // Low nibble of F is always 0
             Register::B => self.registers.bc = (self.registers.bc & 0x00FF) | ((value as u16) << 8),
             Register::C => self.registers.bc = (self.registers.bc & 0xFF00) | (value as u16),
             Register::D => self.registers.de = (self.registers.de & 0x00FF) | ((value as u16) << 8),
@@ -161,35 +324,195 @@ impl Cpu
         }
     }
 
-    pub fn logical_and_register_u8(&mut self, byte: u8, register: Register)
+    pub fn load_inter_register(&mut self, target: Register, source: Register)
     {
-        match register
+        let value: u8 = self.get_register(source);
+
+        self.set_register(target, value);
+    }
+
+    /// Adds a value (and optionally the carry flag) to A, storing the result in A.
+    /// Sets Z if the result is 0, clears N, sets H and C on carry out of bit 3 and bit 7 respectively.
+    pub fn alu_add(&mut self, value: u8, with_carry: bool)
+    {
+        let a = self.get_register(Register::A);
+        let carry = (with_carry && self.get_flag(Flag::C)) as u8;
+        let result = a.wrapping_add(value).wrapping_add(carry);
+
+        self.set_multi_flags(
+            (result == 0) as i8,
+            0,
+            ((a & 0x0F) + (value & 0x0F) + carry > 0x0F) as i8,
+            (a as u16 + value as u16 + carry as u16 > 0xFF) as i8
+        );
+
+        self.set_register(Register::A, result);
+    }
+
+    /// Subtracts a value (and optionally the carry flag) from A, storing the result in A unless this is a compare.
+    /// Sets Z if the result is 0, sets N, sets H and C on borrow from bit 4 and bit 8 respectively.
+    pub fn alu_sub(&mut self, value: u8, with_carry: bool, store: bool)
+    {
+        let a = self.get_register(Register::A);
+        let carry = (with_carry && self.get_flag(Flag::C)) as u8;
+        let result = a.wrapping_sub(value).wrapping_sub(carry);
+
+        self.set_multi_flags(
+            (result == 0) as i8,
+            1,
+            ((a & 0x0F) < (value & 0x0F) + carry) as i8,
+            ((a as u16) < value as u16 + carry as u16) as i8
+        );
+
+        if store
         {
-            Register::A => self.registers.af &= ((byte as u16) << 8) | 0x00FF,
-            Register::F => self.registers.af &= 0xFF00 | byte as u16,
-            Register::B => self.registers.af &= ((byte as u16) << 8) | 0x00FF,
-            Register::C => self.registers.af &= 0xFF00 | byte as u16,
-            Register::D => self.registers.af &= ((byte as u16) << 8) | 0x00FF,
-            Register::E => self.registers.af &= 0xFF00 | byte as u16,
-            Register::H => self.registers.af &= ((byte as u16) << 8) | 0x00FF,
-            Register::L => self.registers.af &= 0xFF00 | byte as u16,
+            self.set_register(Register::A, result);
         }
     }
 
-    pub fn logical_and_inter_register(&mut self, register: Register)
+    /// Take the logical AND of register A and the given value, and store the result in A
+    /// Sets Z if A == 0, sets N, H, and C as 0, 1, 0 respectively.
+    pub fn alu_and(&mut self, value: u8)
     {
-        // The target will always be register A
-        match register
+        let result = self.get_register(Register::A) & value;
+
+        self.set_register(Register::A, result);
+        self.set_multi_flags((result == 0) as i8, 0, 1, 0);
+    }
+
+    pub fn alu_xor(&mut self, value: u8)
+    {
+        let result = self.get_register(Register::A) ^ value;
+
+        self.set_register(Register::A, result);
+        self.set_multi_flags((result == 0) as i8, 0, 0, 0);
+    }
+
+    pub fn alu_or(&mut self, value: u8)
+    {
+        let result = self.get_register(Register::A) | value;
+
+        self.set_register(Register::A, result);
+        self.set_multi_flags((result == 0) as i8, 0, 0, 0);
+    }
+
+    /// Increments an 8-bit value. Sets Z if the result is 0, clears N, sets H on carry out of bit 3, and preserves C.
+    pub fn alu_inc(&mut self, value: u8) -> u8
+    {
+        let result = value.wrapping_add(1);
+
+        self.set_multi_flags((result == 0) as i8, 0, (value & 0x0F == 0x0F) as i8, -1);
+
+        result
+    }
+
+    /// Decrements an 8-bit value. Sets Z if the result is 0, sets N, sets H on borrow from bit 4, and preserves C.
+    pub fn alu_dec(&mut self, value: u8) -> u8
+    {
+        let result = value.wrapping_sub(1);
+
+        self.set_multi_flags((result == 0) as i8, 1, (value & 0x0F == 0x00) as i8, -1);
+
+        result
+    }
+
+    /// Adds a 16-bit value to HL. Preserves Z, clears N, sets H and C on carry out of bit 11 and bit 15 respectively.
+    pub fn alu_add_hl(&mut self, value: u16)
+    {
+        let hl = self.registers.hl;
+
+        self.set_multi_flags(
+            -1,
+            0,
+            ((hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF) as i8,
+            (hl as u32 + value as u32 > 0xFFFF) as i8
+        );
+
+        self.registers.hl = hl.wrapping_add(value);
+    }
+
+    /// Computes SP plus a signed offset, as used by ADD SP, r8 and LD HL, SP + r8.
+    /// Clears Z and N, and sets H and C from the unsigned addition of the low byte.
+    pub fn alu_sp_offset(&mut self, offset: u8) -> u16
+    {
+        let sp = self.registers.sp;
+
+        self.set_multi_flags(
+            0,
+            0,
+            ((sp & 0x000F) + (offset as u16 & 0x000F) > 0x000F) as i8,
+            ((sp & 0x00FF) + offset as u16 > 0x00FF) as i8
+        );
+
+        sp.wrapping_add(offset as i8 as u16)
+    }
+
+    /// Rotates a value left by one bit. Bit 7 goes into C, and bit 0 takes either the old bit 7 or the old carry.
+    /// Sets Z if the result is 0 and clears N and H.
+    pub fn alu_rotate_left(&mut self, value: u8, through_carry: bool) -> u8
+    {
+        let bit0 = match through_carry
         {
-            Register::A => self.logical_and_register_u8((self.registers.af >> 8) as u8, Register::A),
-            Register::F => self.logical_and_register_u8(self.registers.af as u8, Register::A),
-            Register::B => self.logical_and_register_u8((self.registers.bc >> 8) as u8, Register::A),
-            Register::C => self.logical_and_register_u8(self.registers.bc as u8, Register::A),
-            Register::D => self.logical_and_register_u8((self.registers.de >> 8) as u8, Register::A),
-            Register::E => self.logical_and_register_u8(self.registers.de as u8, Register::A),
-            Register::H => self.logical_and_register_u8((self.registers.hl >> 8) as u8, Register::A),
-            Register::L => self.logical_and_register_u8(self.registers.hl as u8, Register::A),
-        }
+            true => self.get_flag(Flag::C) as u8,
+            false => value >> 7
+        };
+        let result = (value << 1) | bit0;
+
+        self.set_multi_flags((result == 0) as i8, 0, 0, (value >> 7) as i8);
+
+        result
+    }
+
+    /// Rotates a value right by one bit. Bit 0 goes into C, and bit 7 takes either the old bit 0 or the old carry.
+    /// Sets Z if the result is 0 and clears N and H.
+    pub fn alu_rotate_right(&mut self, value: u8, through_carry: bool) -> u8
+    {
+        let bit7 = match through_carry
+        {
+            true => self.get_flag(Flag::C) as u8,
+            false => value & 0x01
+        };
+        let result = (value >> 1) | (bit7 << 7);
+
+        self.set_multi_flags((result == 0) as i8, 0, 0, (value & 0x01) as i8);
+
+        result
+    }
+
+    /// Shifts a value left by one bit (SLA). Bit 7 goes into C and bit 0 is cleared.
+    /// Sets Z if the result is 0 and clears N and H.
+    pub fn alu_shift_left(&mut self, value: u8) -> u8
+    {
+        let result = value << 1;
+
+        self.set_multi_flags((result == 0) as i8, 0, 0, (value >> 7) as i8);
+
+        result
+    }
+
+    /// Shifts a value right by one bit. Bit 0 goes into C, and bit 7 is either kept (SRA) or cleared (SRL).
+    /// Sets Z if the result is 0 and clears N and H.
+    pub fn alu_shift_right(&mut self, value: u8, arithmetic: bool) -> u8
+    {
+        let result = match arithmetic
+        {
+            true => (value >> 1) | (value & 0x80),
+            false => value >> 1
+        };
+
+        self.set_multi_flags((result == 0) as i8, 0, 0, (value & 0x01) as i8);
+
+        result
+    }
+
+    /// Swaps the upper and lower nibbles of a value. Sets Z if the result is 0 and clears N, H and C.
+    pub fn alu_swap(&mut self, value: u8) -> u8
+    {
+        let result = value.rotate_left(4);
+
+        self.set_multi_flags((result == 0) as i8, 0, 0, 0);
+
+        result
     }
 
     /// Sets, clears, or preserves all flags. 0 = clear, 1 = set, anything else preserves.
@@ -226,10 +549,10 @@ impl Cpu
 
     pub fn print_state(&self, opcode: u8)
     {
-        println!("PC: ${:0>4X} | Cache: {: <6} {} {} | Registers: AF: 0x{:0>4X}, BC: 0x{:0>4X}, DE: 0x{:0>4X}: HL: 0x{:0>4X} | Flags: Z: [{}], N: [{}], H: [{}], C: [{}]", 
-            self.registers.pc - (lookups::instruction_len(&opcode) as u16 - 1 /* TODO Band-aid fix? */),
-            lookups::grand_opcode_lookup(opcode).1,
-            if self.cache.len() > 0
+        println!("PC: ${:0>4X} | Cache: {: <18} {} {} | Registers: AF: 0x{:0>4X}, BC: 0x{:0>4X}, DE: 0x{:0>4X}: HL: 0x{:0>4X} | Flags: Z: [{}], N: [{}], H: [{}], C: [{}]",
+            self.registers.pc.wrapping_sub(opcodes::instruction_len(opcode) as u16),
+            disassembler::disassemble(opcode, &self.cache),
+            if !self.cache.is_empty()
             {
                 format!("0x{:0>2X}", self.cache[0])
             } else {
@@ -251,4 +574,60 @@ impl Cpu
             match self.get_flag(Flag::C) { true => "X", false => " " },
         );
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Runs a one-byte ALU instruction on A and B, and returns A and F afterwards
+    fn run_alu(opcode: u8, a: u8, b: u8, flags: u8) -> (u8, u8)
+    {
+        let mut cpu = Cpu::new();
+        cpu.registers.af = ((a as u16) << 8) | flags as u16;
+        cpu.set_register(Register::B, b);
+
+        let _ = cpu.execute(opcode);
+
+        ((cpu.registers.af >> 8) as u8, cpu.registers.af as u8)
+    }
+
+    #[test]
+    fn clear_flag_keeps_accumulator()
+    {
+        let mut cpu = Cpu::new();
+        cpu.registers.af = 0xABF0;
+
+        cpu.clear_flag(Flag::Z);
+        assert_eq!(cpu.registers.af, 0xAB70);
+
+        cpu.clear_flag(Flag::C);
+        assert_eq!(cpu.registers.af, 0xAB60);
+    }
+
+    #[test]
+    fn and_keeps_result()
+    {
+        // AND B: Z0 N0 H1 C0
+        assert_eq!(run_alu(0xA0, 0b1100_1010, 0b1010_0110, 0xF0), (0b1000_0010, 0x20));
+        assert_eq!(run_alu(0xA0, 0xF0, 0x0F, 0x00), (0x00, 0xA0));
+    }
+
+    #[test]
+    fn xor_keeps_result()
+    {
+        // XOR B: Z only
+        assert_eq!(run_alu(0xA8, 0xFF, 0x0F, 0xF0), (0xF0, 0x00));
+        assert_eq!(run_alu(0xA8, 0x5A, 0x5A, 0x70), (0x00, 0x80));
+    }
+
+    #[test]
+    fn add_keeps_result()
+    {
+        // ADD A, B: N0, Z/H/C from the result
+        assert_eq!(run_alu(0x80, 0x0F, 0x01, 0xF0), (0x10, 0x20));
+        assert_eq!(run_alu(0x80, 0x3A, 0xC6, 0x00), (0x00, 0xB0));
+        assert_eq!(run_alu(0x80, 0x12, 0x34, 0xF0), (0x46, 0x00));
+    }
+}
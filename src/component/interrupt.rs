@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+use crate::component::bus::MemoryMapped;
+
+/// Interrupt sources, by bit position in IE and IF. Lower bits have higher priority.
+#[derive(Clone, Copy)]
+pub enum Interrupt
+{
+    VBlank = 0,
+    Stat = 1,
+    Timer = 2,
+    Serial = 3,
+    Joypad = 4
+}
+
+/// IE ($FFFF) and IF ($FF0F). Components raise requests in IF, and the CPU services whichever are also enabled in IE.
+pub struct InterruptController
+{
+    pub enabled: u8,
+    pub requested: u8
+}
+
+impl Default for InterruptController
+{
+    fn default() -> Self
+    {
+        InterruptController::new()
+    }
+}
+
+impl InterruptController
+{
+    pub fn new() -> Self
+    {
+        InterruptController
+        {
+            enabled: 0x00,
+            requested: 0xE1 // VBlank is already requested when the boot ROM hands over
+        }
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt)
+    {
+        self.requested |= 1 << interrupt as u8;
+    }
+
+    /// Interrupts that are both requested and enabled
+    pub fn pending(&self) -> u8
+    {
+        self.enabled & self.requested & 0x1F
+    }
+
+    /// Clears the highest priority pending interrupt and returns its handler address, if there is one
+    pub fn acknowledge(&mut self) -> Option<u16>
+    {
+        match self.pending()
+        {
+            0 => None,
+            pending => {
+                let bit = pending.trailing_zeros() as u16;
+
+                self.requested &= !(1 << bit);
+
+                Some(0x0040 + bit * 8)
+            }
+        }
+    }
+}
+
+impl MemoryMapped for InterruptController
+{
+    fn read(&self, address: u16) -> u8
+    {
+        match address
+        {
+            0xFF0F => self.requested | 0xE0, // Upper 3 bits are unused and read as 1
+            0xFFFF => self.enabled,
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            0xFF0F => self.requested = data & 0x1F,
+            0xFFFF => self.enabled = data,
+            _ => { }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn acknowledges_in_priority_order()
+    {
+        let mut interrupts = InterruptController::new();
+        interrupts.requested = 0x00;
+        interrupts.enabled = 0x1F;
+
+        interrupts.request(Interrupt::Joypad);
+        interrupts.request(Interrupt::Timer);
+        interrupts.request(Interrupt::Stat);
+
+        assert_eq!(interrupts.acknowledge(), Some(0x0048));
+        assert_eq!(interrupts.acknowledge(), Some(0x0050));
+        assert_eq!(interrupts.acknowledge(), Some(0x0060));
+        assert_eq!(interrupts.acknowledge(), None);
+    }
+
+    #[test]
+    fn skips_requests_that_are_not_enabled()
+    {
+        let mut interrupts = InterruptController::new();
+        interrupts.requested = 0x00;
+        interrupts.enabled = 0x04;
+
+        interrupts.request(Interrupt::VBlank);
+        interrupts.request(Interrupt::Timer);
+
+        assert_eq!(interrupts.acknowledge(), Some(0x0050));
+        assert_eq!(interrupts.acknowledge(), None);
+
+        // VBlank stays requested until it's enabled
+        assert_eq!(interrupts.read(0xFF0F), 0xE1);
+    }
+}
@@ -0,0 +1,166 @@
+#![allow(dead_code)]
+
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::component::interrupt::{Interrupt, InterruptController};
+
+/// A set of pressed buttons. Combine with `|`, e.g. `Buttons::A | Buttons::START`.
+///
+/// The low nibble holds the action buttons and the high nibble the d-pad, each in P1 bit order.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Buttons(u8);
+
+impl Buttons
+{
+    pub const A: Buttons = Buttons(0x01);
+    pub const B: Buttons = Buttons(0x02);
+    pub const SELECT: Buttons = Buttons(0x04);
+    pub const START: Buttons = Buttons(0x08);
+    pub const RIGHT: Buttons = Buttons(0x10);
+    pub const LEFT: Buttons = Buttons(0x20);
+    pub const UP: Buttons = Buttons(0x40);
+    pub const DOWN: Buttons = Buttons(0x80);
+
+    pub const fn empty() -> Self
+    {
+        Buttons(0x00)
+    }
+
+    pub const fn bits(&self) -> u8
+    {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u8) -> Self
+    {
+        Buttons(bits)
+    }
+
+    pub const fn is_empty(&self) -> bool
+    {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: Buttons) -> bool
+    {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Buttons)
+    {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Buttons)
+    {
+        self.0 &= !other.0;
+    }
+
+    pub fn set(&mut self, other: Buttons, pressed: bool)
+    {
+        match pressed
+        {
+            true => self.insert(other),
+            false => self.remove(other)
+        }
+    }
+}
+
+impl BitOr for Buttons
+{
+    type Output = Buttons;
+
+    fn bitor(self, rhs: Buttons) -> Buttons
+    {
+        Buttons(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Buttons
+{
+    fn bitor_assign(&mut self, rhs: Buttons)
+    {
+        self.0 |= rhs.0;
+    }
+}
+
+/// P1/JOYP ($FF00). The game selects the d-pad (bit 4 low), the action buttons (bit 5 low) or both, and reads the
+/// selected buttons back in the low nibble, where 0 means pressed.
+pub struct Joypad
+{
+    /// Bits 4 and 5 as last written
+    select: u8,
+    pressed: Buttons
+}
+
+impl Default for Joypad
+{
+    fn default() -> Self
+    {
+        Joypad::new()
+    }
+}
+
+impl Joypad
+{
+    pub fn new() -> Self
+    {
+        Joypad
+        {
+            select: 0x30,
+            pressed: Buttons::empty()
+        }
+    }
+
+    /// Low nibble as the game sees it, active low
+    fn lines(&self) -> u8
+    {
+        let mut lines = 0x0F;
+
+        if self.select & 0x10 == 0
+        {
+            lines &= !(self.pressed.bits() >> 4);
+        }
+
+        if self.select & 0x20 == 0
+        {
+            lines &= !self.pressed.bits() & 0x0F;
+        }
+
+        lines
+    }
+
+    /// Runs a change to the input lines, and requests the joypad interrupt if any of them went from high to low
+    fn update(&mut self, interrupts: &mut InterruptController, change: impl FnOnce(&mut Self))
+    {
+        let old_lines = self.lines();
+        change(self);
+
+        if old_lines & !self.lines() != 0
+        {
+            interrupts.request(Interrupt::Joypad);
+        }
+    }
+
+    pub fn pressed(&self) -> Buttons
+    {
+        self.pressed
+    }
+
+    pub fn read(&self) -> u8
+    {
+        0xC0 | self.select | self.lines() // Bits 6 and 7 are unused and read as 1
+    }
+
+    pub fn set_buttons(&mut self, buttons: Buttons, interrupts: &mut InterruptController)
+    {
+        self.update(interrupts, | joypad | joypad.pressed = buttons);
+    }
+
+    /// Handles a write to P1. Only the select bits are writable, and selecting a row with a button held counts as an
+    /// input line going low.
+    pub fn write_select(&mut self, data: u8, interrupts: &mut InterruptController)
+    {
+        self.update(interrupts, | joypad | joypad.select = data & 0x30);
+    }
+}
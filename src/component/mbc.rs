@@ -0,0 +1,662 @@
+#![allow(dead_code)]
+
+use crate::component::{cartridge::header::CartridgeType, rtc::{Rtc, ClockSource, SystemClock}};
+
+/// Memory bank controller. Sits between the bus and the cartridge's ROM and RAM, and decides which bank an address
+/// lands in. Writes to `$0000-$7FFF` don't reach ROM, they program the controller's registers instead.
+pub trait Mbc
+{
+    /// Reads from `$0000-$7FFF`
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8;
+
+    /// Reads from `$A000-$BFFF`
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8;
+
+    /// Writes to `$A000-$BFFF`. Returns whether the write was stored, in RAM or a clock register, rather than dropped
+    /// because RAM is disabled or missing.
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) -> bool;
+
+    /// Writes to `$0000-$7FFF`
+    fn write_register(&mut self, address: u16, data: u8);
+
+    /// Size in bytes of any RAM built into the controller itself, rather than declared in the header
+    fn builtin_ram_size(&self) -> usize
+    {
+        0
+    }
+
+    /// Advances any timekeeping hardware on the cartridge by a number of CPU cycles
+    fn tick(&mut self, _cycles: u32) { }
+
+    /// Extra state to append to the battery save after RAM, such as the RTC footer
+    fn save_footer(&self) -> Option<Vec<u8>>
+    {
+        None
+    }
+
+    /// Restores the extra state written by `save_footer`
+    fn load_footer(&mut self, _footer: &[u8]) { }
+}
+
+const ROM_BANK_SIZE: usize = 0x4000;
+const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Picks a memory bank controller for a cartridge type. Returns None for controllers that aren't emulated. Rumble is
+/// ignored, so rumble carts get a plain MBC5.
+pub fn mbc_for(cart_type: CartridgeType) -> Option<Box<dyn Mbc>>
+{
+    mbc_with_clock(cart_type, Box::new(SystemClock))
+}
+
+/// Same as `mbc_for`, but a real-time clock reads wall-clock time from the given source instead of the host clock
+pub fn mbc_with_clock(cart_type: CartridgeType, clock: Box<dyn ClockSource>) -> Option<Box<dyn Mbc>>
+{
+    let mbc: Box<dyn Mbc> = match cart_type
+    {
+        CartridgeType::RomOnly | CartridgeType::RomRam | CartridgeType::RomRamBattery => Box::new(RomOnly),
+        CartridgeType::Mbc1 | CartridgeType::Mbc1Ram | CartridgeType::Mbc1RamBattery => Box::new(Mbc1::new()),
+        CartridgeType::Mbc2 | CartridgeType::Mbc2Battery => Box::new(Mbc2::new()),
+        CartridgeType::Mbc3TimerBattery | CartridgeType::Mbc3TimerRamBattery => Box::new(Mbc3::with_clock(clock)),
+        CartridgeType::Mbc3 | CartridgeType::Mbc3Ram | CartridgeType::Mbc3RamBattery => Box::new(Mbc3::new()),
+        CartridgeType::Mbc5 | CartridgeType::Mbc5Ram | CartridgeType::Mbc5RamBattery
+        | CartridgeType::Mbc5Rumble | CartridgeType::Mbc5RumbleRam | CartridgeType::Mbc5RumbleRamBattery => Box::new(Mbc5::new()),
+        _ => return None
+    };
+
+    Some(mbc)
+}
+
+/// Reads a byte from a 16KiB ROM bank, wrapping the bank number around the size of the ROM like the unconnected
+/// upper address lines would
+fn rom_byte(rom: &[u8], bank: usize, address: u16) -> u8
+{
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    let offset = (bank % banks) * ROM_BANK_SIZE + (address as usize & (ROM_BANK_SIZE - 1));
+
+    *rom.get(offset).unwrap_or(&0xFF)
+}
+
+/// Resolves an address in `$A000-$BFFF` to an offset into external RAM, wrapping the bank number around the size of
+/// the RAM. Returns None if the cartridge has no RAM.
+fn ram_offset(ram: &[u8], bank: usize, address: u16) -> Option<usize>
+{
+    if ram.is_empty()
+    {
+        return None;
+    }
+
+    let banks = ram.len().div_ceil(RAM_BANK_SIZE);
+
+    Some(((bank % banks) * RAM_BANK_SIZE + (address as usize & (RAM_BANK_SIZE - 1))) % ram.len())
+}
+
+/// Cartridges with no controller: 32KiB of ROM and optionally up to 8KiB of RAM, both mapped directly
+pub struct RomOnly;
+
+impl Mbc for RomOnly
+{
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8
+    {
+        *rom.get(address as usize).unwrap_or(&0xFF)
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8
+    {
+        match ram_offset(ram, 0, address)
+        {
+            Some(offset) => ram[offset],
+            None => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) -> bool
+    {
+        match ram_offset(ram, 0, address)
+        {
+            Some(offset) => {
+                ram[offset] = data;
+                true
+            },
+            None => false
+        }
+    }
+
+    fn write_register(&mut self, _address: u16, _data: u8) { }
+}
+
+/// MBC1: up to 2MiB ROM and 32KiB RAM. The 2-bit register at `$4000-$5FFF` either selects the RAM bank or supplies
+/// bits 5-6 of the ROM bank, depending on the banking mode.
+pub struct Mbc1
+{
+    ram_enabled: bool,
+    /// Lower 5 bits of the ROM bank number
+    rom_bank: u8,
+    /// RAM bank, or upper 2 bits of the ROM bank number
+    upper_bank: u8,
+    /// false = simple banking mode (mode 0), true = advanced banking mode (mode 1)
+    advanced_mode: bool
+}
+
+impl Default for Mbc1
+{
+    fn default() -> Self
+    {
+        Mbc1::new()
+    }
+}
+
+impl Mbc1
+{
+    pub fn new() -> Self
+    {
+        Mbc1
+        {
+            ram_enabled: false,
+            rom_bank: 1,
+            upper_bank: 0,
+            advanced_mode: false
+        }
+    }
+
+    fn ram_bank(&self) -> usize
+    {
+        match self.advanced_mode
+        {
+            true => self.upper_bank as usize,
+            false => 0
+        }
+    }
+}
+
+impl Mbc for Mbc1
+{
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8
+    {
+        match address
+        {
+            0x0000..=0x3FFF => {
+                // In advanced mode the upper bits also apply to the first bank area
+                let bank = match self.advanced_mode
+                {
+                    true => (self.upper_bank as usize) << 5,
+                    false => 0
+                };
+
+                rom_byte(rom, bank, address)
+            },
+            _ => rom_byte(rom, ((self.upper_bank as usize) << 5) | self.rom_bank as usize, address)
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8
+    {
+        match (self.ram_enabled, ram_offset(ram, self.ram_bank(), address))
+        {
+            (true, Some(offset)) => ram[offset],
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) -> bool
+    {
+        match (self.ram_enabled, ram_offset(ram, self.ram_bank(), address))
+        {
+            (true, Some(offset)) => {
+                ram[offset] = data;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // Bank 0 can't be selected here. The check happens on the 5-bit value, which is why banks
+                // 0x20, 0x40 and 0x60 can't be reached in simple mode.
+                self.rom_bank = match data & 0x1F
+                {
+                    0 => 1,
+                    bank => bank
+                };
+            },
+            0x4000..=0x5FFF => self.upper_bank = data & 0x03,
+            _ => self.advanced_mode = data & 0x01 == 0x01
+        }
+    }
+}
+
+/// MBC2: up to 256KiB ROM, with 512 half-bytes of RAM built into the controller
+pub struct Mbc2
+{
+    ram_enabled: bool,
+    rom_bank: u8
+}
+
+impl Default for Mbc2
+{
+    fn default() -> Self
+    {
+        Mbc2::new()
+    }
+}
+
+impl Mbc2
+{
+    pub fn new() -> Self
+    {
+        Mbc2
+        {
+            ram_enabled: false,
+            rom_bank: 1
+        }
+    }
+}
+
+impl Mbc for Mbc2
+{
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8
+    {
+        match address
+        {
+            0x0000..=0x3FFF => rom_byte(rom, 0, address),
+            _ => rom_byte(rom, self.rom_bank as usize, address)
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8
+    {
+        // Only the lower 9 address bits are decoded, so the 512 bytes echo through the whole area.
+        // Only the low nibble is stored; the upper nibble reads as open bus.
+        match (self.ram_enabled, ram.get((address & 0x01FF) as usize))
+        {
+            (true, Some(byte)) => *byte | 0xF0,
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) -> bool
+    {
+        match (self.ram_enabled, ram.get_mut((address & 0x01FF) as usize))
+        {
+            (true, Some(byte)) => {
+                *byte = data & 0x0F;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8)
+    {
+        // Bit 8 of the address picks between the RAM enable and the ROM bank registers
+        if let 0x0000..=0x3FFF = address
+        {
+            match address & 0x0100
+            {
+                0 => self.ram_enabled = data & 0x0F == 0x0A,
+                _ => {
+                    self.rom_bank = match data & 0x0F
+                    {
+                        0 => 1,
+                        bank => bank
+                    };
+                }
+            }
+        }
+    }
+
+    fn builtin_ram_size(&self) -> usize
+    {
+        512
+    }
+}
+
+/// MBC3: up to 2MiB ROM and 32KiB RAM, with a 7-bit ROM bank number and an optional real-time clock
+pub struct Mbc3
+{
+    ram_enabled: bool,
+    rom_bank: u8,
+    /// RAM bank 0x00-0x03, or 0x08-0x0C to map a clock register
+    ram_bank: u8,
+    rtc: Option<Rtc>
+}
+
+impl Default for Mbc3
+{
+    fn default() -> Self
+    {
+        Mbc3::new()
+    }
+}
+
+impl Mbc3
+{
+    pub fn new() -> Self
+    {
+        Mbc3
+        {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rtc: None
+        }
+    }
+
+    /// Creates an MBC3 with a real-time clock, reading wall-clock time from the given source
+    pub fn with_clock(clock: Box<dyn ClockSource>) -> Self
+    {
+        Mbc3
+        {
+            rtc: Some(Rtc::new(clock)),
+            ..Mbc3::new()
+        }
+    }
+}
+
+impl Mbc for Mbc3
+{
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8
+    {
+        match address
+        {
+            0x0000..=0x3FFF => rom_byte(rom, 0, address),
+            _ => rom_byte(rom, self.rom_bank as usize, address)
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8
+    {
+        match (self.ram_enabled, self.ram_bank, &self.rtc)
+        {
+            (true, 0x00..=0x07, _) => match ram_offset(ram, self.ram_bank as usize, address)
+            {
+                Some(offset) => ram[offset],
+                None => 0xFF
+            },
+            (true, 0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) -> bool
+    {
+        match (self.ram_enabled, self.ram_bank, &mut self.rtc)
+        {
+            (true, 0x00..=0x07, _) => match ram_offset(ram, self.ram_bank as usize, address)
+            {
+                Some(offset) => {
+                    ram[offset] = data;
+                    true
+                },
+                None => false
+            },
+            (true, 0x08..=0x0C, Some(rtc)) => {
+                rtc.write(self.ram_bank, data);
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            0x0000..=0x1FFF => self.ram_enabled = data & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match data & 0x7F
+                {
+                    0 => 1,
+                    bank => bank
+                };
+            },
+            0x4000..=0x5FFF => self.ram_bank = data & 0x0F,
+            _ => {
+                if let Some(rtc) = &mut self.rtc
+                {
+                    rtc.write_latch(data);
+                }
+            }
+        }
+    }
+
+    fn tick(&mut self, cycles: u32)
+    {
+        if let Some(rtc) = &mut self.rtc
+        {
+            rtc.tick(cycles);
+        }
+    }
+
+    fn save_footer(&self) -> Option<Vec<u8>>
+    {
+        self.rtc.as_ref().map(| rtc | rtc.save_footer())
+    }
+
+    fn load_footer(&mut self, footer: &[u8])
+    {
+        if let Some(rtc) = &mut self.rtc
+        {
+            rtc.load_footer(footer);
+        }
+    }
+}
+
+/// MBC5: up to 8MiB ROM and 128KiB RAM. The ROM bank number is 9 bits, and unlike the older controllers bank 0 can
+/// be mapped into `$4000-$7FFF`.
+pub struct Mbc5
+{
+    ram_enabled: bool,
+    rom_bank: u16,
+    ram_bank: u8
+}
+
+impl Default for Mbc5
+{
+    fn default() -> Self
+    {
+        Mbc5::new()
+    }
+}
+
+impl Mbc5
+{
+    pub fn new() -> Self
+    {
+        Mbc5
+        {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0
+        }
+    }
+}
+
+impl Mbc for Mbc5
+{
+    fn read_rom(&self, rom: &[u8], address: u16) -> u8
+    {
+        match address
+        {
+            0x0000..=0x3FFF => rom_byte(rom, 0, address),
+            _ => rom_byte(rom, self.rom_bank as usize, address)
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], address: u16) -> u8
+    {
+        match (self.ram_enabled, ram_offset(ram, self.ram_bank as usize, address))
+        {
+            (true, Some(offset)) => ram[offset],
+            _ => 0xFF
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], address: u16, data: u8) -> bool
+    {
+        match (self.ram_enabled, ram_offset(ram, self.ram_bank as usize, address))
+        {
+            (true, Some(offset)) => {
+                ram[offset] = data;
+                true
+            },
+            _ => false
+        }
+    }
+
+    fn write_register(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            // Unlike the older controllers, MBC5 decodes the whole byte, so 0x1A doesn't enable RAM
+            0x0000..=0x1FFF => self.ram_enabled = data == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | data as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0x0FF) | (((data & 0x01) as u16) << 8),
+            0x4000..=0x5FFF => self.ram_bank = data & 0x0F,
+            _ => { }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// A ROM where the first two bytes of each bank hold its bank number, low byte first
+    fn banked_rom(banks: usize) -> Vec<u8>
+    {
+        let mut rom = vec![0x00; banks * ROM_BANK_SIZE];
+
+        for bank in 0..banks
+        {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+            rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+        }
+
+        rom
+    }
+
+    /// The bank mapped at an address, going by the numbers `banked_rom` wrote
+    fn bank_at(mbc: &dyn Mbc, rom: &[u8], address: u16) -> usize
+    {
+        mbc.read_rom(rom, address) as usize | (mbc.read_rom(rom, address + 1) as usize) << 8
+    }
+
+    #[test]
+    fn mbc1_maps_bank_0_to_1()
+    {
+        let rom = banked_rom(128);
+        let mut mbc = Mbc1::new();
+
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 1);
+
+        // Only the low 5 bits are checked, so 0x20 also turns into 0x21
+        mbc.write_register(0x4000, 0x01);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x21);
+
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x25);
+        assert_eq!(bank_at(&mbc, &rom, 0x0000), 0);
+    }
+
+    #[test]
+    fn mbc1_mode_1_applies_upper_bits_to_bank_0_and_ram()
+    {
+        let rom = banked_rom(128);
+        let mut ram = vec![0x00; 0x8000];
+        let mut mbc = Mbc1::new();
+
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x02);
+        assert!(mbc.write_ram(&mut ram, 0xA000, 0x11));
+
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(bank_at(&mbc, &rom, 0x0000), 0x40);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x41);
+        assert!(mbc.write_ram(&mut ram, 0xA000, 0x22));
+
+        // Mode 0 always used RAM bank 0, mode 1 picked bank 2
+        assert_eq!(ram[0x0000], 0x11);
+        assert_eq!(ram[0x4000], 0x22);
+    }
+
+    #[test]
+    fn mbc2_stores_512_nibbles()
+    {
+        let mut ram = vec![0x00; 512];
+        let mut mbc = Mbc2::new();
+
+        assert!(!mbc.write_ram(&mut ram, 0xA000, 0x5A));
+
+        mbc.write_register(0x0000, 0x0A);
+        assert!(mbc.write_ram(&mut ram, 0xA001, 0x5A));
+
+        // Only the low nibble is kept, and the 512 bytes echo through the whole area
+        assert_eq!(ram[1], 0x0A);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0xFA);
+        assert_eq!(mbc.read_ram(&ram, 0xA201), 0xFA);
+        assert_eq!(mbc.read_ram(&ram, 0xBE01), 0xFA);
+    }
+
+    #[test]
+    fn mbc2_picks_register_by_a8()
+    {
+        let rom = banked_rom(16);
+        let mut mbc = Mbc2::new();
+
+        // A8 set: ROM bank, even with a value that would enable RAM
+        mbc.write_register(0x0100, 0x0A);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x0A);
+        assert_eq!(mbc.read_ram(&[0x00; 512], 0xA000), 0xFF);
+
+        // A8 clear: RAM enable, leaving the ROM bank alone
+        mbc.write_register(0x2000, 0x0A);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x0A);
+        assert_eq!(mbc.read_ram(&[0x00; 512], 0xA000), 0xF0);
+
+        mbc.write_register(0x2100, 0x00);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 1);
+    }
+
+    #[test]
+    fn mbc5_has_a_9_bit_rom_bank()
+    {
+        let rom = banked_rom(512);
+        let mut mbc = Mbc5::new();
+
+        mbc.write_register(0x2000, 0x05);
+        mbc.write_register(0x3000, 0x01);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x105);
+
+        mbc.write_register(0x2000, 0xFF);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0x1FF);
+
+        // Bank 0 can be mapped in
+        mbc.write_register(0x2000, 0x00);
+        mbc.write_register(0x3000, 0x00);
+        assert_eq!(bank_at(&mbc, &rom, 0x4000), 0);
+    }
+
+    #[test]
+    fn mbc5_ram_enable_checks_the_whole_byte()
+    {
+        let mut ram = vec![0x00; 0x2000];
+        let mut mbc = Mbc5::new();
+
+        for data in [0x1A, 0xFA, 0x0B]
+        {
+            mbc.write_register(0x0000, data);
+            assert!(!mbc.write_ram(&mut ram, 0xA000, 0x12));
+        }
+
+        mbc.write_register(0x0000, 0x0A);
+        assert!(mbc.write_ram(&mut ram, 0xA000, 0x12));
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x12);
+    }
+}
@@ -1,2 +1,10 @@
+pub mod apu;
+pub mod bus;
 pub mod cartridge;
 pub mod cpu;
+pub mod interrupt;
+pub mod joypad;
+pub mod mbc;
+pub mod ppu;
+pub mod rtc;
+pub mod timer;
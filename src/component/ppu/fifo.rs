@@ -0,0 +1,322 @@
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+
+use crate::component::ppu::{Ppu, Sprite, SCREEN_WIDTH};
+
+/// Dots spent on each of the fetcher's tile, data low and data high steps
+const FETCH_STEP_DOTS: u8 = 2;
+
+/// Dots a sprite fetch takes once the background fetcher has a tile ready
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FetchStep
+{
+    Tile,
+    DataLow,
+    DataHigh,
+    /// Waiting for the background FIFO to empty so the fetched row can be pushed
+    Push
+}
+
+#[derive(Clone, Copy, Default)]
+struct SpritePixel
+{
+    color: u8,
+    /// OBP1 rather than OBP0
+    palette: bool,
+    /// BG-over-OBJ, the sprite only shows over background colour 0
+    bg_priority: bool
+}
+
+/// State of the pixel FIFO back end during mode 3. Pixels go out to the LCD one per dot, and the fetcher refills the
+/// background FIFO 8 pixels at a time. Sprite fetches stall the output, and the window restarts the fetcher, so mode
+/// 3 ends up as long as it is on hardware and registers written mid-line take effect from the next pixel.
+pub struct PixelFifo
+{
+    background: VecDeque<u8>,
+    sprites: VecDeque<SpritePixel>,
+
+    step: FetchStep,
+    step_dots: u8,
+    /// Tile column the fetcher is on, relative to the start of the line or the window
+    tile_x: u8,
+    row_address: u16,
+    data_low: u8,
+    data_high: u8,
+    /// The first fetch of every line is thrown away
+    first_fetch: bool,
+
+    /// Pixels pushed to the LCD so far this line
+    lx: u8,
+    /// Pixels left to throw away for fine scrolling (SCX & 7)
+    discard: u8,
+    window_active: bool,
+    window_drawn: bool,
+
+    /// Sprites found by the OAM scan that haven't been fetched yet, by X
+    line_sprites: VecDeque<Sprite>,
+    /// Sprite being fetched and how many dots of the fetch are left
+    sprite_fetch: Option<(Sprite, u8)>
+}
+
+impl Default for PixelFifo
+{
+    fn default() -> Self
+    {
+        PixelFifo::new()
+    }
+}
+
+impl PixelFifo
+{
+    pub fn new() -> Self
+    {
+        PixelFifo
+        {
+            background: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            step: FetchStep::Tile,
+            step_dots: 0,
+            tile_x: 0,
+            row_address: 0,
+            data_low: 0,
+            data_high: 0,
+            first_fetch: true,
+            lx: 0,
+            discard: 0,
+            window_active: false,
+            window_drawn: false,
+            line_sprites: VecDeque::new(),
+            sprite_fetch: None
+        }
+    }
+
+    fn restart_fetcher(&mut self)
+    {
+        self.step = FetchStep::Tile;
+        self.step_dots = 0;
+        self.tile_x = 0;
+    }
+}
+
+impl Ppu
+{
+    /// Resets the FIFO for a new line, at the start of mode 3
+    pub(super) fn fifo_start_line(&mut self)
+    {
+        let mut sprites = self.scan_oam();
+        sprites.sort_by_key(| s | (s.x, s.index));
+
+        self.fifo = PixelFifo::new();
+        self.fifo.discard = self.scx & 0x07;
+        self.fifo.line_sprites = sprites.into();
+    }
+
+    /// Advances mode 3 by one dot. Returns true once all 160 pixels of the line have been pushed.
+    pub(super) fn fifo_step(&mut self) -> bool
+    {
+        if let Some((sprite, dots)) = self.fifo.sprite_fetch
+        {
+            // The background fetcher finishes its current tile before the sprite fetch can start
+            if self.fifo.step != FetchStep::Push
+            {
+                self.fetcher_step();
+                return false;
+            }
+
+            match dots
+            {
+                1 => {
+                    self.load_sprite(&sprite);
+                    self.fifo.sprite_fetch = None;
+                },
+                _ => self.fifo.sprite_fetch = Some((sprite, dots - 1))
+            }
+
+            return false;
+        }
+
+        if !self.fifo.window_active && self.window_visible() && self.fifo.lx as u16 + 7 >= self.wx as u16
+        {
+            self.fifo.window_active = true;
+            self.fifo.window_drawn = true;
+            self.fifo.background.clear();
+            self.fifo.restart_fetcher();
+
+            // A window left of the screen edge has its first columns cut off
+            self.fifo.discard = 7u8.saturating_sub(self.wx);
+        }
+
+        self.fetcher_step();
+
+        if self.lcdc & 0x02 != 0 && self.fifo.discard == 0 && !self.fifo.background.is_empty()
+        {
+            if let Some(sprite) = self.fifo.line_sprites.front().copied()
+            {
+                if sprite.x <= self.fifo.lx + 8
+                {
+                    self.fifo.line_sprites.pop_front();
+                    self.fifo.sprite_fetch = Some((sprite, SPRITE_FETCH_DOTS));
+                    return false;
+                }
+            }
+        }
+
+        let Some(bg_color) = self.fifo.background.pop_front() else
+        {
+            return false;
+        };
+
+        if self.fifo.discard > 0
+        {
+            self.fifo.discard -= 1;
+            return false;
+        }
+
+        let sprite = self.fifo.sprites.pop_front().unwrap_or_default();
+        let bg_color = match self.lcdc & 0x01
+        {
+            0 => 0,
+            _ => bg_color
+        };
+
+        let sprite_visible = sprite.color != 0 && self.lcdc & 0x02 != 0 && !(sprite.bg_priority && bg_color != 0);
+
+        let shade = match (sprite_visible, sprite.palette)
+        {
+            (true, false) => Ppu::apply_palette(self.obp0, sprite.color),
+            (true, true) => Ppu::apply_palette(self.obp1, sprite.color),
+            (false, _) => Ppu::apply_palette(self.bgp, bg_color)
+        };
+
+        self.framebuffer[self.ly as usize * SCREEN_WIDTH + self.fifo.lx as usize] = shade;
+        self.fifo.lx += 1;
+
+        if self.fifo.lx as usize == SCREEN_WIDTH
+        {
+            if self.fifo.window_drawn
+            {
+                self.window_line += 1;
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Advances the background/window fetcher by one dot. SCX, SCY and LCDC are read as each step happens.
+    fn fetcher_step(&mut self)
+    {
+        let fifo = &mut self.fifo;
+
+        if fifo.step != FetchStep::Push
+        {
+            fifo.step_dots += 1;
+
+            if fifo.step_dots < FETCH_STEP_DOTS
+            {
+                return;
+            }
+
+            fifo.step_dots = 0;
+        }
+
+        match self.fifo.step
+        {
+            FetchStep::Tile => {
+                let (map_base, map_x, map_y) = match self.fifo.window_active
+                {
+                    true => {
+                        let map = match self.lcdc & 0x40 { 0 => 0x9800, _ => 0x9C00 };
+                        (map, self.fifo.tile_x & 0x1F, self.window_line)
+                    },
+                    false => {
+                        let map = match self.lcdc & 0x08 { 0 => 0x9800, _ => 0x9C00 };
+                        (map, ((self.scx >> 3) + self.fifo.tile_x) & 0x1F, self.ly.wrapping_add(self.scy))
+                    }
+                };
+
+                let tile = self.vram_byte(map_base + (map_y as u16 / 8) * 32 + map_x as u16);
+
+                self.fifo.row_address = self.bg_tile_row_address(tile, map_y % 8);
+                self.fifo.step = FetchStep::DataLow;
+            },
+            FetchStep::DataLow => {
+                self.fifo.data_low = self.vram_byte(self.fifo.row_address);
+                self.fifo.step = FetchStep::DataHigh;
+            },
+            FetchStep::DataHigh => {
+                self.fifo.data_high = self.vram_byte(self.fifo.row_address + 1);
+                self.fifo.step = FetchStep::Push;
+            },
+            FetchStep::Push => {
+                if !self.fifo.background.is_empty()
+                {
+                    return;
+                }
+
+                self.fifo.step = FetchStep::Tile;
+
+                if self.fifo.first_fetch
+                {
+                    self.fifo.first_fetch = false;
+                    return;
+                }
+
+                for bit in (0..8).rev()
+                {
+                    let color = (((self.fifo.data_high >> bit) & 0x01) << 1) | ((self.fifo.data_low >> bit) & 0x01);
+                    self.fifo.background.push_back(color);
+                }
+
+                self.fifo.tile_x = self.fifo.tile_x.wrapping_add(1);
+            }
+        }
+    }
+
+    /// Mixes a fetched sprite row into the sprite FIFO. Pixels already there came from a sprite with higher priority,
+    /// so only their transparent slots get filled.
+    fn load_sprite(&mut self, sprite: &Sprite)
+    {
+        let row_address = self.sprite_row_address(sprite);
+
+        for px in 0..8u8
+        {
+            let screen_x = sprite.x as i16 - 8 + px as i16;
+
+            // Off the left edge of the screen, or already pushed
+            if screen_x < self.fifo.lx as i16
+            {
+                continue;
+            }
+
+            let tile_x = match sprite.attributes & 0x20
+            {
+                0 => px,
+                _ => 7 - px
+            };
+
+            let pixel = SpritePixel
+            {
+                color: self.tile_pixel(row_address, tile_x),
+                palette: sprite.attributes & 0x10 != 0,
+                bg_priority: sprite.attributes & 0x80 != 0
+            };
+
+            let slot = (screen_x - self.fifo.lx as i16) as usize;
+
+            while self.fifo.sprites.len() <= slot
+            {
+                self.fifo.sprites.push_back(SpritePixel::default());
+            }
+
+            if self.fifo.sprites[slot].color == 0
+            {
+                self.fifo.sprites[slot] = pixel;
+            }
+        }
+    }
+}
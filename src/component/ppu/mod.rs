@@ -0,0 +1,633 @@
+#![allow(dead_code)]
+
+mod fifo;
+
+use crate::component::{bus::MemoryMapped, interrupt::{Interrupt, InterruptController}};
+use fifo::PixelFifo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+/// Dots (T-cycles) per scanline, including HBlank
+const DOTS_PER_LINE: u16 = 456;
+/// Scanlines per frame, including the 10 lines of VBlank
+const LINES_PER_FRAME: u8 = 154;
+/// Dots per frame, 59.7275 frames per second
+pub const DOTS_PER_FRAME: u32 = DOTS_PER_LINE as u32 * LINES_PER_FRAME as u32;
+/// Length of the OAM scan
+const MODE2_DOTS: u16 = 80;
+/// Length of pixel transfer in the scanline renderer. The real length varies, see the FIFO back end for that.
+const MODE3_DOTS: u16 = 172;
+
+/// Maximum number of sprites drawn on one line
+const SPRITES_PER_LINE: usize = 10;
+
+/// How the PPU draws pixels
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PpuBackend
+{
+    /// Draws each line all at once at the end of a fixed-length mode 3. Fast, but misses mid-line register writes.
+    Scanline,
+    /// Pushes pixels out one dot at a time through the background and sprite FIFOs, with a variable-length mode 3
+    Fifo
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode
+{
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Transfer = 3
+}
+
+/// A sprite as stored in OAM
+#[derive(Clone, Copy)]
+pub struct Sprite
+{
+    /// Screen Y + 16
+    pub y: u8,
+    /// Screen X + 8
+    pub x: u8,
+    pub tile: u8,
+    pub attributes: u8,
+    /// Position in OAM, breaks priority ties between sprites at the same X
+    pub index: u8
+}
+
+/// Pixel processing unit. Owns VRAM, OAM and the LCD registers, and draws one scanline at a time into a 160x144
+/// framebuffer of shade indices (0 = white, 3 = black).
+pub struct Ppu
+{
+    pub vram: [u8; 0x2000],
+    pub oam: [u8; 0xA0],
+
+    pub lcdc: u8,
+    pub stat: u8,
+    pub scy: u8,
+    pub scx: u8,
+    pub ly: u8,
+    pub lyc: u8,
+    pub bgp: u8,
+    pub obp0: u8,
+    pub obp1: u8,
+    pub wy: u8,
+    pub wx: u8,
+
+    mode: Mode,
+    /// Dots into the current line
+    dot: u16,
+    /// Line of the window to draw next. Only advances on lines where the window was actually drawn.
+    window_line: u8,
+    /// Whether WY has matched LY at some point this frame
+    window_triggered: bool,
+    /// Previous state of the STAT interrupt line, interrupts fire on its rising edge
+    stat_line: bool,
+
+    framebuffer: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    frame_ready: bool,
+    /// Dots since the LCD was turned off, or since the last blank frame while it stays off
+    off_dots: u32,
+
+    backend: PpuBackend,
+    fifo: PixelFifo
+}
+
+impl Default for Ppu
+{
+    fn default() -> Self
+    {
+        Ppu::new()
+    }
+}
+
+impl Ppu
+{
+    pub fn new() -> Self
+    {
+        Ppu::with_backend(PpuBackend::Scanline)
+    }
+
+    pub fn with_backend(backend: PpuBackend) -> Self
+    {
+        Ppu
+        {
+            vram: [0; 0x2000],
+            oam: [0; 0xA0],
+            lcdc: 0x91,
+            stat: 0x85,
+            scy: 0x00,
+            scx: 0x00,
+            ly: 0x00,
+            lyc: 0x00,
+            bgp: 0xFC,
+            obp0: 0xFF,
+            obp1: 0xFF,
+            wy: 0x00,
+            wx: 0x00,
+            mode: Mode::OamScan,
+            dot: 0,
+            window_line: 0,
+            window_triggered: false,
+            stat_line: false,
+            framebuffer: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frame_ready: false,
+            off_dots: 0,
+            backend,
+            fifo: PixelFifo::new()
+        }
+    }
+
+    pub fn lcd_enabled(&self) -> bool
+    {
+        self.lcdc & 0x80 != 0
+    }
+
+    pub fn mode(&self) -> Mode
+    {
+        self.mode
+    }
+
+    /// The last completed frame, row by row, as shade indices
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT]
+    {
+        &self.framebuffer
+    }
+
+    /// Returns true once per frame, when the PPU enters VBlank
+    pub fn take_frame_ready(&mut self) -> bool
+    {
+        std::mem::replace(&mut self.frame_ready, false)
+    }
+
+    /// Advances the PPU by a number of dots (T-cycles)
+    pub fn tick(&mut self, dots: u32, interrupts: &mut InterruptController)
+    {
+        if !self.lcd_enabled()
+        {
+            // The screen stays blank, but frames keep coming at the usual rate for anything paced by them
+            self.off_dots += dots;
+
+            if self.off_dots >= DOTS_PER_FRAME
+            {
+                self.off_dots -= DOTS_PER_FRAME;
+                self.framebuffer.fill(0);
+                self.frame_ready = true;
+            }
+
+            return;
+        }
+
+        for _ in 0..dots
+        {
+            self.step_dot(interrupts);
+        }
+    }
+
+    fn step_dot(&mut self, interrupts: &mut InterruptController)
+    {
+        self.dot += 1;
+
+        match self.mode
+        {
+            Mode::OamScan if self.dot == MODE2_DOTS => {
+                if self.backend == PpuBackend::Fifo
+                {
+                    self.fifo_start_line();
+                }
+
+                self.set_mode(Mode::Transfer, interrupts);
+            },
+            Mode::Transfer => {
+                let finished = match self.backend
+                {
+                    PpuBackend::Scanline => self.dot == MODE2_DOTS + MODE3_DOTS,
+                    PpuBackend::Fifo => self.fifo_step()
+                };
+
+                if finished
+                {
+                    if self.backend == PpuBackend::Scanline
+                    {
+                        self.render_scanline();
+                    }
+
+                    self.set_mode(Mode::HBlank, interrupts);
+                }
+            },
+            _ => { }
+        }
+
+        if self.dot < DOTS_PER_LINE
+        {
+            return;
+        }
+
+        // End of line
+        self.dot = 0;
+        self.ly += 1;
+
+        if self.ly == LINES_PER_FRAME
+        {
+            self.ly = 0;
+            self.window_line = 0;
+            self.window_triggered = false;
+        }
+
+        match self.ly
+        {
+            0..=143 => self.set_mode(Mode::OamScan, interrupts),
+            144 => {
+                self.frame_ready = true;
+                interrupts.request(Interrupt::VBlank);
+                self.set_mode(Mode::VBlank, interrupts);
+            },
+            _ => self.update_stat_line(interrupts)
+        }
+    }
+
+    fn set_mode(&mut self, mode: Mode, interrupts: &mut InterruptController)
+    {
+        self.mode = mode;
+
+        if mode == Mode::OamScan && self.ly == self.wy
+        {
+            self.window_triggered = true;
+        }
+
+        self.update_stat_line(interrupts);
+    }
+
+    /// Recomputes the STAT interrupt line from the current mode and LY=LYC, and requests the interrupt on a rising
+    /// edge. Because the sources are ORed together, one source being high blocks another from firing (STAT blocking).
+    fn update_stat_line(&mut self, interrupts: &mut InterruptController)
+    {
+        let line = (self.stat & 0x40 != 0 && self.ly == self.lyc)
+            || (self.stat & 0x20 != 0 && self.mode == Mode::OamScan)
+            || (self.stat & 0x10 != 0 && self.mode == Mode::VBlank)
+            || (self.stat & 0x08 != 0 && self.mode == Mode::HBlank);
+
+        if line && !self.stat_line
+        {
+            interrupts.request(Interrupt::Stat);
+        }
+
+        self.stat_line = line;
+    }
+
+    /// Reads a byte from VRAM by its bus address
+    pub fn vram_byte(&self, address: u16) -> u8
+    {
+        self.vram[(address & 0x1FFF) as usize]
+    }
+
+    /// Address of a row of a background or window tile, using the addressing mode picked by LCDC bit 4
+    pub fn bg_tile_row_address(&self, tile: u8, row: u8) -> u16
+    {
+        let base = match self.lcdc & 0x10
+        {
+            0 => (0x9000i32 + (tile as i8 as i32) * 16) as u16, // $8800 method, signed tile index
+            _ => 0x8000 + tile as u16 * 16
+        };
+
+        base + row as u16 * 2
+    }
+
+    /// Colour index (0-3) of one pixel in a row of tile data
+    pub fn tile_pixel(&self, row_address: u16, x: u8) -> u8
+    {
+        let low = self.vram_byte(row_address);
+        let high = self.vram_byte(row_address + 1);
+        let bit = 7 - x;
+
+        (((high >> bit) & 0x01) << 1) | ((low >> bit) & 0x01)
+    }
+
+    /// Maps a colour index through a palette register to a shade
+    pub fn apply_palette(palette: u8, color: u8) -> u8
+    {
+        (palette >> (color * 2)) & 0x03
+    }
+
+    /// Up to 10 sprites that overlap the current line, in OAM order
+    pub fn scan_oam(&self) -> Vec<Sprite>
+    {
+        let height = self.sprite_height();
+
+        (0..40u8)
+            .map(| i | {
+                let entry = &self.oam[i as usize * 4..i as usize * 4 + 4];
+                Sprite { y: entry[0], x: entry[1], tile: entry[2], attributes: entry[3], index: i }
+            })
+            .filter(| s | {
+                let top = s.y as i16 - 16;
+                (self.ly as i16) >= top && (self.ly as i16) < top + height as i16
+            })
+            .take(SPRITES_PER_LINE)
+            .collect()
+    }
+
+    pub fn sprite_height(&self) -> u8
+    {
+        match self.lcdc & 0x04
+        {
+            0 => 8,
+            _ => 16
+        }
+    }
+
+    /// Address of the row of sprite tile data that falls on the current line, taking flips and 8x16 mode into account
+    pub fn sprite_row_address(&self, sprite: &Sprite) -> u16
+    {
+        let height = self.sprite_height();
+        let mut row = (self.ly as i16 - (sprite.y as i16 - 16)) as u8;
+
+        if sprite.attributes & 0x40 != 0
+        {
+            row = height - 1 - row;
+        }
+
+        let tile = match height
+        {
+            16 => sprite.tile & 0xFE,
+            _ => sprite.tile
+        };
+
+        0x8000 + tile as u16 * 16 + row as u16 * 2
+    }
+
+    fn window_visible(&self) -> bool
+    {
+        self.lcdc & 0x20 != 0 && self.window_triggered && self.wx <= 166
+    }
+
+    /// Draws the current line into the framebuffer
+    fn render_scanline(&mut self)
+    {
+        let ly = self.ly as usize;
+
+        // Colour index of the background/window under each pixel, kept for sprite priority
+        let mut bg_colors = [0u8; SCREEN_WIDTH];
+        let bg_enabled = self.lcdc & 0x01 != 0;
+        let window_visible = bg_enabled && self.window_visible();
+        let mut window_drawn = false;
+
+        for (x, bg_color) in bg_colors.iter_mut().enumerate()
+        {
+            if !bg_enabled
+            {
+                break;
+            }
+
+            let in_window = window_visible && x as i16 >= self.wx as i16 - 7;
+
+            let (map_base, map_x, map_y) = match in_window
+            {
+                true => {
+                    window_drawn = true;
+
+                    let map = match self.lcdc & 0x40 { 0 => 0x9800, _ => 0x9C00 };
+                    (map, (x as i16 - (self.wx as i16 - 7)) as u8, self.window_line)
+                },
+                false => {
+                    let map = match self.lcdc & 0x08 { 0 => 0x9800, _ => 0x9C00 };
+                    (map, (x as u8).wrapping_add(self.scx), self.ly.wrapping_add(self.scy))
+                }
+            };
+
+            let tile = self.vram_byte(map_base + (map_y as u16 / 8) * 32 + map_x as u16 / 8);
+            let row_address = self.bg_tile_row_address(tile, map_y % 8);
+
+            *bg_color = self.tile_pixel(row_address, map_x % 8);
+        }
+
+        if window_drawn
+        {
+            self.window_line += 1;
+        }
+
+        let mut line = bg_colors.map(| color | Ppu::apply_palette(self.bgp, color));
+
+        if self.lcdc & 0x02 != 0
+        {
+            let mut sprites = self.scan_oam();
+
+            // Lower X wins, ties go to the earlier OAM entry
+            sprites.sort_by_key(| s | (s.x, s.index));
+
+            // The winning sprite pixel under each x: its colour and attributes. The first opaque pixel claims the spot,
+            // and only then does its BG-over-OBJ bit decide whether the background hides it, so a sprite behind the
+            // background still hides any lower priority sprite under it.
+            let mut sprite_pixels: [Option<(u8, u8)>; SCREEN_WIDTH] = [None; SCREEN_WIDTH];
+
+            for sprite in &sprites
+            {
+                let row_address = self.sprite_row_address(sprite);
+
+                for px in 0..8u8
+                {
+                    let screen_x = sprite.x as i16 - 8 + px as i16;
+
+                    if !(0..SCREEN_WIDTH as i16).contains(&screen_x) || sprite_pixels[screen_x as usize].is_some()
+                    {
+                        continue;
+                    }
+
+                    let tile_x = match sprite.attributes & 0x20
+                    {
+                        0 => px,
+                        _ => 7 - px
+                    };
+
+                    let color = self.tile_pixel(row_address, tile_x);
+
+                    // Colour 0 is transparent
+                    if color != 0
+                    {
+                        sprite_pixels[screen_x as usize] = Some((color, sprite.attributes));
+                    }
+                }
+            }
+
+            for (x, pixel) in sprite_pixels.iter().enumerate()
+            {
+                let (color, attributes) = match pixel
+                {
+                    Some(pixel) => *pixel,
+                    None => continue
+                };
+
+                // BG-over-OBJ: sprite only shows over background colour 0
+                if attributes & 0x80 != 0 && bg_colors[x] != 0
+                {
+                    continue;
+                }
+
+                let palette = match attributes & 0x10
+                {
+                    0 => self.obp0,
+                    _ => self.obp1
+                };
+
+                line[x] = Ppu::apply_palette(palette, color);
+            }
+        }
+
+        self.framebuffer[ly * SCREEN_WIDTH..(ly + 1) * SCREEN_WIDTH].copy_from_slice(&line);
+    }
+}
+
+impl MemoryMapped for Ppu
+{
+    fn read(&self, address: u16) -> u8
+    {
+        match address
+        {
+            0x8000..=0x9FFF => self.vram[(address - 0x8000) as usize],
+            0xFE00..=0xFE9F => self.oam[(address - 0xFE00) as usize],
+            0xFF40 => self.lcdc,
+            0xFF41 => {
+                let coincidence = ((self.ly == self.lyc) as u8) << 2;
+                let mode = match self.lcd_enabled()
+                {
+                    true => self.mode as u8,
+                    false => 0
+                };
+
+                0x80 | (self.stat & 0x78) | coincidence | mode
+            },
+            0xFF42 => self.scy,
+            0xFF43 => self.scx,
+            0xFF44 => self.ly,
+            0xFF45 => self.lyc,
+            0xFF47 => self.bgp,
+            0xFF48 => self.obp0,
+            0xFF49 => self.obp1,
+            0xFF4A => self.wy,
+            0xFF4B => self.wx,
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8)
+    {
+        match address
+        {
+            0x8000..=0x9FFF => self.vram[(address - 0x8000) as usize] = data,
+            0xFE00..=0xFE9F => self.oam[(address - 0xFE00) as usize] = data,
+            0xFF40 => {
+                let was_enabled = self.lcd_enabled();
+                self.lcdc = data;
+
+                // Turning the LCD off resets to the top of the frame, turning it back on starts a fresh one
+                if was_enabled && !self.lcd_enabled()
+                {
+                    self.off_dots = 0;
+                    self.ly = 0;
+                    self.dot = 0;
+                    self.mode = Mode::HBlank;
+                    self.window_line = 0;
+                    self.window_triggered = false;
+                } else if !was_enabled && self.lcd_enabled() {
+                    self.mode = Mode::OamScan;
+                    self.window_triggered = self.ly == self.wy;
+                }
+            },
+            0xFF41 => self.stat = data & 0x78, // Only the interrupt enable bits are writable
+            0xFF42 => self.scy = data,
+            0xFF43 => self.scx = data,
+            0xFF44 => { }, // LY is read-only
+            0xFF45 => self.lyc = data,
+            0xFF47 => self.bgp = data,
+            0xFF48 => self.obp0 = data,
+            0xFF49 => self.obp1 = data,
+            0xFF4A => self.wy = data,
+            0xFF4B => self.wx = data,
+            _ => { }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// A PPU on line 0 with a solid background of colour 1 (tile 0) and two solid sprite tiles, 1 in colour 3 and
+    /// 2 in colour 2. Identity palettes, so the framebuffer holds raw colour indices.
+    fn ppu_with_tiles() -> Ppu
+    {
+        let mut ppu = Ppu::new();
+        ppu.lcdc = 0x93; // LCD, BG and sprites on, tile data at $8000
+        ppu.bgp = 0xE4;
+        ppu.obp0 = 0xE4;
+        ppu.obp1 = 0xE4;
+
+        for row in 0..8
+        {
+            ppu.vram[row * 2] = 0xFF;
+            ppu.vram[row * 2 + 1] = 0x00;
+            ppu.vram[16 + row * 2] = 0xFF;
+            ppu.vram[16 + row * 2 + 1] = 0xFF;
+            ppu.vram[32 + row * 2] = 0x00;
+            ppu.vram[32 + row * 2 + 1] = 0xFF;
+        }
+
+        ppu
+    }
+
+    fn set_sprite(ppu: &mut Ppu, index: usize, x: u8, tile: u8, attributes: u8)
+    {
+        ppu.oam[index * 4..index * 4 + 4].copy_from_slice(&[16, x, tile, attributes]);
+    }
+
+    fn line(ppu: &Ppu) -> &[u8]
+    {
+        &ppu.framebuffer()[..SCREEN_WIDTH]
+    }
+
+    #[test]
+    fn sprite_behind_background_hides_lower_priority_sprite()
+    {
+        let mut ppu = ppu_with_tiles();
+
+        // Sprite 0 in front (lower X) is behind the background, sprite 1 overlaps it from 4 pixels to the right
+        set_sprite(&mut ppu, 0, 8, 1, 0x80);
+        set_sprite(&mut ppu, 1, 12, 2, 0x00);
+
+        ppu.render_scanline();
+
+        // Where they overlap sprite 0 wins, and the background covers it, so sprite 1 never shows
+        assert_eq!(line(&ppu)[0..8], [1; 8]);
+        assert_eq!(line(&ppu)[8..12], [2; 4]);
+    }
+
+    #[test]
+    fn sprite_behind_background_shows_over_colour_0()
+    {
+        let mut ppu = ppu_with_tiles();
+        ppu.vram[0..16].fill(0x00);
+
+        set_sprite(&mut ppu, 0, 8, 1, 0x80);
+        set_sprite(&mut ppu, 1, 12, 2, 0x00);
+
+        ppu.render_scanline();
+
+        assert_eq!(line(&ppu)[0..8], [3; 8]);
+        assert_eq!(line(&ppu)[8..12], [2; 4]);
+        assert_eq!(line(&ppu)[12], 0);
+    }
+
+    #[test]
+    fn earlier_oam_entry_wins_at_same_x()
+    {
+        let mut ppu = ppu_with_tiles();
+
+        set_sprite(&mut ppu, 0, 8, 2, 0x00);
+        set_sprite(&mut ppu, 1, 8, 1, 0x00);
+
+        ppu.render_scanline();
+
+        assert_eq!(line(&ppu)[0..8], [2; 8]);
+    }
+}
@@ -0,0 +1,395 @@
+#![allow(dead_code)]
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// CPU clock rate, and so the number of cycles in one RTC second
+const CYCLES_PER_SECOND: u32 = 4_194_304;
+
+/// Size of the RTC footer appended to battery saves, as written by BGB, SameBoy, mGBA and VBA-M
+pub const FOOTER_LEN: usize = 48;
+
+/// Older variant of the footer with a 32-bit timestamp
+pub const SHORT_FOOTER_LEN: usize = 44;
+
+/// Where the RTC gets wall-clock time from, to catch up on time that passed while the emulator wasn't running
+pub trait ClockSource
+{
+    /// Current time as seconds since the Unix epoch
+    fn now(&self) -> u64;
+}
+
+/// Host system clock
+pub struct SystemClock;
+
+impl ClockSource for SystemClock
+{
+    fn now(&self) -> u64
+    {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(| d | d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct RtcRegisters
+{
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    /// Lower 8 bits of the 9-bit day counter
+    day_low: u8,
+    /// Bit 0: day counter bit 8, bit 6: halt, bit 7: day counter carry
+    day_high: u8
+}
+
+/// MBC3 real-time clock. Counts emulated time while running, and host time (from the clock source) in between runs.
+pub struct Rtc
+{
+    current: RtcRegisters,
+    latched: RtcRegisters,
+    /// Last value written to the latch register, the latch happens on a 0x00 -> 0x01 sequence
+    latch_state: u8,
+    /// Cycles elapsed since the last whole second
+    subsecond_cycles: u32,
+    clock: Box<dyn ClockSource>
+}
+
+impl Rtc
+{
+    pub fn new(clock: Box<dyn ClockSource>) -> Self
+    {
+        Rtc
+        {
+            current: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            latch_state: 0xFF,
+            subsecond_cycles: 0,
+            clock
+        }
+    }
+
+    fn halted(&self) -> bool
+    {
+        self.current.day_high & 0x40 != 0
+    }
+
+    /// Reads the latched copy of a clock register (0x08-0x0C)
+    pub fn read(&self, register: u8) -> u8
+    {
+        match register
+        {
+            0x08 => self.latched.seconds & 0x3F,
+            0x09 => self.latched.minutes & 0x3F,
+            0x0A => self.latched.hours & 0x1F,
+            0x0B => self.latched.day_low,
+            0x0C => self.latched.day_high & 0xC1,
+            _ => 0xFF
+        }
+    }
+
+    /// Writes a clock register (0x08-0x0C). Writes go straight to the live counters.
+    pub fn write(&mut self, register: u8, data: u8)
+    {
+        match register
+        {
+            0x08 => {
+                self.current.seconds = data & 0x3F;
+                self.subsecond_cycles = 0; // Writing seconds resets the internal prescaler
+            },
+            0x09 => self.current.minutes = data & 0x3F,
+            0x0A => self.current.hours = data & 0x1F,
+            0x0B => self.current.day_low = data,
+            0x0C => self.current.day_high = data & 0xC1,
+            _ => { }
+        }
+    }
+
+    /// Handles a write to `$6000-$7FFF`. Writing 0x00 then 0x01 copies the live counters into the latched registers.
+    pub fn write_latch(&mut self, data: u8)
+    {
+        if self.latch_state == 0x00 && data == 0x01
+        {
+            self.latched = self.current;
+        }
+
+        self.latch_state = data;
+    }
+
+    /// Advances the clock by a number of CPU cycles
+    pub fn tick(&mut self, cycles: u32)
+    {
+        if self.halted()
+        {
+            return;
+        }
+
+        self.subsecond_cycles += cycles;
+
+        if self.subsecond_cycles >= CYCLES_PER_SECOND
+        {
+            self.advance_seconds((self.subsecond_cycles / CYCLES_PER_SECOND) as u64);
+            self.subsecond_cycles %= CYCLES_PER_SECOND;
+        }
+    }
+
+    /// Advances the clock by a number of whole seconds, all at once rather than second by second
+    pub fn advance_seconds(&mut self, seconds: u64)
+    {
+        if self.halted()
+        {
+            return;
+        }
+
+        let rtc = &mut self.current;
+
+        let (value, minutes) = count(rtc.seconds, 60, 0x40, seconds);
+        rtc.seconds = value;
+
+        let (value, hours) = count(rtc.minutes, 60, 0x40, minutes);
+        rtc.minutes = value;
+
+        let (value, days) = count(rtc.hours, 24, 0x20, hours);
+        rtc.hours = value;
+
+        let days = ((((rtc.day_high & 0x01) as u64) << 8) | rtc.day_low as u64) + days;
+
+        rtc.day_low = days as u8;
+        rtc.day_high = (rtc.day_high & 0xFE) | ((days >> 8) & 0x01) as u8;
+
+        if days > 0x1FF
+        {
+            rtc.day_high |= 0x80; // Day counter carry, stays set until cleared by the game
+        }
+    }
+
+    /// Serializes the clock into the 48-byte save footer: the live registers, the latched registers, each as a
+    /// little-endian u32, followed by a little-endian u64 Unix timestamp
+    pub fn save_footer(&self) -> Vec<u8>
+    {
+        let mut footer = Vec::with_capacity(FOOTER_LEN);
+
+        for registers in [self.current, self.latched]
+        {
+            for value in [registers.seconds, registers.minutes, registers.hours, registers.day_low, registers.day_high]
+            {
+                footer.extend_from_slice(&(value as u32).to_le_bytes());
+            }
+        }
+
+        footer.extend_from_slice(&self.clock.now().to_le_bytes());
+
+        footer
+    }
+
+    /// Restores the clock from a 48 or 44-byte save footer, then counts the time that passed since it was written
+    pub fn load_footer(&mut self, footer: &[u8])
+    {
+        if footer.len() != FOOTER_LEN && footer.len() != SHORT_FOOTER_LEN
+        {
+            return;
+        }
+
+        let field = | i: usize | footer[i * 4];
+
+        self.current = RtcRegisters { seconds: field(0), minutes: field(1), hours: field(2), day_low: field(3), day_high: field(4) };
+        self.latched = RtcRegisters { seconds: field(5), minutes: field(6), hours: field(7), day_low: field(8), day_high: field(9) };
+
+        let timestamp = match footer.len()
+        {
+            FOOTER_LEN => u64::from_le_bytes(footer[40..48].try_into().unwrap()),
+            _ => u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64
+        };
+
+        self.advance_seconds(self.clock.now().saturating_sub(timestamp));
+    }
+}
+
+/// Adds `ticks` to a counter that wraps at `modulus`, returning the new value and how many times it carried into the
+/// next counter. A counter that was written out of range keeps counting until its bit width (`limit`) overflows,
+/// without carrying, and counts normally from zero after that.
+fn count(value: u8, modulus: u64, limit: u64, ticks: u64) -> (u8, u64)
+{
+    let value = value as u64;
+
+    let total = match value < modulus
+    {
+        true => value + ticks,
+        false if ticks < limit - value => return ((value + ticks) as u8, 0),
+        false => ticks - (limit - value)
+    };
+
+    ((total % modulus) as u8, total / modulus)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Clock that's always at the same time
+    struct FixedClock(u64);
+
+    impl ClockSource for FixedClock
+    {
+        fn now(&self) -> u64
+        {
+            self.0
+        }
+    }
+
+    fn rtc_at(now: u64) -> Rtc
+    {
+        Rtc::new(Box::new(FixedClock(now)))
+    }
+
+    /// Latches the live counters and reads back seconds, minutes, hours, day low and day high
+    fn latched(rtc: &mut Rtc) -> [u8; 5]
+    {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+
+        [rtc.read(0x08), rtc.read(0x09), rtc.read(0x0A), rtc.read(0x0B), rtc.read(0x0C)]
+    }
+
+    #[test]
+    fn rolls_over_into_the_next_day()
+    {
+        let mut rtc = rtc_at(0);
+        rtc.write(0x08, 59);
+        rtc.write(0x09, 59);
+        rtc.write(0x0A, 23);
+
+        rtc.advance_seconds(1);
+
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 1, 0]);
+    }
+
+    #[test]
+    fn ticks_whole_seconds_from_cycles()
+    {
+        let mut rtc = rtc_at(0);
+
+        rtc.tick(CYCLES_PER_SECOND - 1);
+        assert_eq!(latched(&mut rtc)[0], 0);
+
+        rtc.tick(1);
+        assert_eq!(latched(&mut rtc)[0], 1);
+    }
+
+    /// Counts one second the way the hardware does, one counter at a time
+    fn step(r: &mut RtcRegisters)
+    {
+        r.seconds = (r.seconds + 1) & 0x3F;
+        if r.seconds != 60 { return; }
+        r.seconds = 0;
+
+        r.minutes = (r.minutes + 1) & 0x3F;
+        if r.minutes != 60 { return; }
+        r.minutes = 0;
+
+        r.hours = (r.hours + 1) & 0x1F;
+        if r.hours != 24 { return; }
+        r.hours = 0;
+
+        let days = ((((r.day_high & 0x01) as u16) << 8) | r.day_low as u16) + 1;
+        r.day_low = days as u8;
+        r.day_high = (r.day_high & 0xFE) | ((days >> 8) & 0x01) as u8 | match days > 0x1FF
+        {
+            true => 0x80,
+            false => 0x00
+        };
+    }
+
+    #[test]
+    fn matches_counting_second_by_second()
+    {
+        // Out of range values count up to their bit width without carrying
+        let start = RtcRegisters { seconds: 62, minutes: 61, hours: 30, day_low: 0xFE, day_high: 0x01 };
+
+        let mut rtc = rtc_at(0);
+        let mut expected = start;
+        rtc.current = start;
+
+        for _ in 0..200_000
+        {
+            step(&mut expected);
+        }
+
+        rtc.advance_seconds(200_000);
+
+        let r = rtc.current;
+        assert_eq!([r.seconds, r.minutes, r.hours, r.day_low, r.day_high], [expected.seconds, expected.minutes, expected.hours, expected.day_low, expected.day_high]);
+    }
+
+    #[test]
+    fn halt_stops_the_clock()
+    {
+        let mut rtc = rtc_at(0);
+        rtc.write(0x0C, 0x40);
+
+        rtc.advance_seconds(1000);
+        rtc.tick(CYCLES_PER_SECOND * 2);
+
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0x40]);
+
+        rtc.write(0x0C, 0x00);
+        rtc.advance_seconds(61);
+
+        assert_eq!(latched(&mut rtc), [1, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn day_counter_carries()
+    {
+        let mut rtc = rtc_at(0);
+        rtc.write(0x0A, 23);
+        rtc.write(0x0B, 0xFF);
+        rtc.write(0x0C, 0x01);
+
+        rtc.advance_seconds(3600);
+
+        // Day 511 wraps to 0 and sets the carry, which stays set through later days
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 0, 0x80]);
+
+        rtc.advance_seconds(86_400 * 3);
+
+        assert_eq!(latched(&mut rtc), [0, 0, 0, 3, 0x80]);
+    }
+
+    #[test]
+    fn footer_round_trips()
+    {
+        let mut saved = rtc_at(1_000_000);
+        saved.write(0x08, 10);
+        saved.write(0x09, 20);
+        saved.write(0x0A, 5);
+        saved.write(0x0B, 0x2A);
+        saved.write(0x0C, 0x01);
+        let latched_before = latched(&mut saved);
+        saved.write(0x08, 11);
+
+        let footer = saved.save_footer();
+        assert_eq!(footer.len(), FOOTER_LEN);
+
+        // Loaded 90 seconds later, the live counters catch up and the latched ones are kept as they were
+        let mut loaded = rtc_at(1_000_090);
+        loaded.load_footer(&footer);
+
+        assert_eq!([loaded.read(0x08), loaded.read(0x09), loaded.read(0x0A), loaded.read(0x0B), loaded.read(0x0C)], latched_before);
+        assert_eq!(latched(&mut loaded), [41, 21, 5, 0x2A, 0x01]);
+    }
+
+    #[test]
+    fn loads_short_footer()
+    {
+        let mut footer = rtc_at(500).save_footer();
+        footer.truncate(SHORT_FOOTER_LEN);
+
+        let mut loaded = rtc_at(503);
+        loaded.load_footer(&footer);
+
+        assert_eq!(latched(&mut loaded)[0], 3);
+    }
+}
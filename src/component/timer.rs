@@ -0,0 +1,253 @@
+#![allow(dead_code)]
+
+use crate::component::{bus::MemoryMapped, interrupt::{Interrupt, InterruptController}};
+
+/// DIV ($FF04), TIMA ($FF05), TMA ($FF06) and TAC ($FF07)
+///
+/// Everything is driven by a 16-bit counter that increments every T-cycle, of which DIV is the upper byte. TIMA
+/// doesn't have its own clock: it increments on a falling edge of one of the counter's bits (picked by TAC), ANDed
+/// with the TAC enable bit. That's why writing DIV or TAC can cause a spurious increment.
+pub struct Timer
+{
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    /// TIMA overflowed this M-cycle. It reads as 0 until the reload happens on the next one.
+    overflow_pending: bool,
+    /// TIMA was reloaded from TMA this M-cycle. Writes to TIMA are ignored, and writes to TMA also go to TIMA.
+    reloading: bool
+}
+
+impl Default for Timer
+{
+    fn default() -> Self
+    {
+        Timer::new()
+    }
+}
+
+impl Timer
+{
+    pub fn new() -> Self
+    {
+        Timer
+        {
+            counter: 0xABCC, // DIV reads 0xAB when the boot ROM hands over
+            tima: 0x00,
+            tma: 0x00,
+            tac: 0xF8,
+            overflow_pending: false,
+            reloading: false
+        }
+    }
+
+    /// Counter bit watched by TIMA for each TAC clock select: 4096Hz, 262144Hz, 65536Hz and 16384Hz
+    fn selected_bit(&self) -> u16
+    {
+        match self.tac & 0x03
+        {
+            0 => 9,
+            1 => 3,
+            2 => 5,
+            _ => 7
+        }
+    }
+
+    /// The signal TIMA watches for a falling edge on
+    fn signal(&self) -> bool
+    {
+        self.tac & 0x04 != 0 && self.counter & (1 << self.selected_bit()) != 0
+    }
+
+    fn increment_tima(&mut self)
+    {
+        let (tima, overflow) = self.tima.overflowing_add(1);
+
+        self.tima = tima;
+        self.overflow_pending = overflow;
+    }
+
+    /// Advances the timer by one M-cycle
+    pub fn step(&mut self, interrupts: &mut InterruptController)
+    {
+        self.reloading = false;
+
+        if self.overflow_pending
+        {
+            self.overflow_pending = false;
+            self.tima = self.tma;
+            self.reloading = true;
+
+            interrupts.request(Interrupt::Timer);
+        }
+
+        let old_signal = self.signal();
+        self.counter = self.counter.wrapping_add(4);
+
+        if old_signal && !self.signal()
+        {
+            self.increment_tima();
+        }
+    }
+}
+
+impl MemoryMapped for Timer
+{
+    fn read(&self, address: u16) -> u8
+    {
+        match address
+        {
+            0xFF04 => (self.counter >> 8) as u8,
+            0xFF05 => self.tima,
+            0xFF06 => self.tma,
+            0xFF07 => self.tac | 0xF8,
+            _ => 0xFF
+        }
+    }
+
+    fn write(&mut self, address: u16, data: u8)
+    {
+        let old_signal = self.signal();
+
+        match address
+        {
+            0xFF04 => self.counter = 0,
+            // Writing during the overflow cycle cancels the reload, writing during the reload cycle is ignored
+            0xFF05 if !self.reloading => {
+                self.tima = data;
+                self.overflow_pending = false;
+            },
+            0xFF06 => {
+                self.tma = data;
+
+                if self.reloading
+                {
+                    self.tima = data;
+                }
+            },
+            0xFF07 => self.tac = data & 0x07,
+            _ => { }
+        }
+
+        // Resetting DIV or changing TAC can pull the watched signal low, which TIMA sees as a falling edge
+        if old_signal && !self.signal()
+        {
+            self.increment_tima();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// A timer at counter 0 with TAC set, and interrupt flags cleared
+    fn timer_with(tac: u8) -> (Timer, InterruptController)
+    {
+        let mut timer = Timer::new();
+        let mut interrupts = InterruptController::new();
+
+        timer.counter = 0;
+        timer.write(0xFF07, tac);
+        interrupts.requested = 0x00;
+
+        (timer, interrupts)
+    }
+
+    fn timer_requested(interrupts: &InterruptController) -> bool
+    {
+        interrupts.requested & (1 << Interrupt::Timer as u8) != 0
+    }
+
+    #[test]
+    fn div_write_on_high_bit_increments_tima()
+    {
+        // 262144Hz watches counter bit 3
+        let (mut timer, _) = timer_with(0x05);
+
+        timer.counter = 0x0008;
+        timer.write(0xFF04, 0x00);
+        assert_eq!(timer.read(0xFF05), 0x01);
+        assert_eq!(timer.read(0xFF04), 0x00);
+
+        // With the bit already low there's no edge
+        timer.counter = 0x0010;
+        timer.write(0xFF04, 0x00);
+        assert_eq!(timer.read(0xFF05), 0x01);
+    }
+
+    #[test]
+    fn tac_change_can_increment_tima()
+    {
+        let (mut timer, _) = timer_with(0x05);
+        timer.counter = 0x0008;
+
+        // Switching to 4096Hz (bit 9, low here) pulls the signal down
+        timer.write(0xFF07, 0x04);
+        assert_eq!(timer.read(0xFF05), 0x01);
+
+        // So does disabling the timer while the watched bit is high
+        timer.counter = 0x0200;
+        timer.write(0xFF07, 0x00);
+        assert_eq!(timer.read(0xFF05), 0x02);
+
+        // Enabling doesn't
+        timer.write(0xFF07, 0x04);
+        assert_eq!(timer.read(0xFF05), 0x02);
+    }
+
+    #[test]
+    fn overflow_reloads_tma_one_cycle_late()
+    {
+        let (mut timer, mut interrupts) = timer_with(0x05);
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF06, 0x42);
+        timer.counter = 0x000C;
+
+        // Bit 3 falls here, and TIMA overflows to 0 without reloading or interrupting yet
+        timer.step(&mut interrupts);
+        assert_eq!(timer.read(0xFF05), 0x00);
+        assert!(!timer_requested(&interrupts));
+
+        timer.step(&mut interrupts);
+        assert_eq!(timer.read(0xFF05), 0x42);
+        assert!(timer_requested(&interrupts));
+    }
+
+    #[test]
+    fn tima_write_cancels_pending_reload()
+    {
+        let (mut timer, mut interrupts) = timer_with(0x05);
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF06, 0x42);
+        timer.counter = 0x000C;
+
+        timer.step(&mut interrupts);
+        timer.write(0xFF05, 0x99);
+
+        timer.step(&mut interrupts);
+        assert_eq!(timer.read(0xFF05), 0x99);
+        assert!(!timer_requested(&interrupts));
+    }
+
+    #[test]
+    fn tima_write_during_reload_is_ignored()
+    {
+        let (mut timer, mut interrupts) = timer_with(0x05);
+        timer.write(0xFF05, 0xFF);
+        timer.write(0xFF06, 0x42);
+        timer.counter = 0x000C;
+
+        timer.step(&mut interrupts);
+        timer.step(&mut interrupts);
+
+        // The reload cycle: TIMA writes are dropped, TMA writes go through to TIMA
+        timer.write(0xFF05, 0x99);
+        assert_eq!(timer.read(0xFF05), 0x42);
+
+        timer.write(0xFF06, 0x77);
+        assert_eq!(timer.read(0xFF05), 0x77);
+    }
+}
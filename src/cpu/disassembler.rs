@@ -0,0 +1,64 @@
+#![allow(dead_code)]
+
+use crate::cpu::opcodes::{self, Opcode};
+
+/// Disassembles a single instruction. `args` holds the bytes following the opcode, i.e. the CB opcode for prefixed
+/// instructions or the immediate operands otherwise.
+pub fn disassemble(opcode: u8, args: &[u8]) -> String
+{
+    let (entry, args): (&Opcode, &[u8]) = match opcode
+    {
+        0xCB => match args.first()
+        {
+            Some(ext_opcode) => (opcodes::lookup_prefixed(*ext_opcode), &args[1..]),
+            None => return opcodes::lookup(opcode).mnemonic.to_string()
+        },
+        _ => (opcodes::lookup(opcode), args)
+    };
+
+    let mut text = entry.mnemonic.to_string();
+    let mut separator = " ";
+
+    for operand in entry.operands
+    {
+        let value = match operand.name
+        {
+            "d8" | "a8" => format!("${:0>2X}", byte(args, 0)),
+            "d16" | "a16" => format!("${:0>4X}", ((byte(args, 1) as u16) << 8) | byte(args, 0) as u16),
+            "r8" => format!("{}", byte(args, 0) as i8),
+            name => name.to_string()
+        };
+
+        let value = match (operand.increment, operand.decrement)
+        {
+            (true, _) => format!("{}+", value),
+            (_, true) => format!("{}-", value),
+            _ => value
+        };
+
+        let value = match (operand.immediate, operand.name)
+        {
+            (false, "a8") => format!("($FF{})", value.trim_start_matches('$')),
+            (false, "C") => "($FF00+C)".to_string(),
+            (false, _) => format!("({})", value),
+            (true, _) => value
+        };
+
+        text.push_str(separator);
+        text.push_str(&value);
+
+        // LD HL, SP+r8 is listed as two operands, SP+ and r8
+        separator = match operand.increment && operand.immediate
+        {
+            true => "",
+            false => ", "
+        };
+    }
+
+    text
+}
+
+fn byte(args: &[u8], index: usize) -> u8
+{
+    args.get(index).copied().unwrap_or(0)
+}
@@ -1 +1,3 @@
+pub mod disassembler;
+pub mod opcodes;
 pub mod operations;
@@ -0,0 +1,92 @@
+#![allow(dead_code)]
+
+// Decode tables, generated at build time from Opcodes.json by build.rs
+
+pub struct Opcode
+{
+    pub mnemonic: &'static str,
+    /// Length of the instruction in bytes. Prefixed instructions count the 0xCB prefix.
+    pub bytes: u8,
+    /// T-cycle cost as [branch taken, branch not taken]. Both are the same for unconditional instructions.
+    pub cycles: [u8; 2],
+    pub operands: &'static [Operand],
+    /// Effect on Z, N, H and C, in that order
+    pub flags: [FlagEffect; 4]
+}
+
+pub struct Operand
+{
+    /// Register, condition, or immediate kind (d8, d16, a8, a16, r8) as named in Opcodes.json
+    pub name: &'static str,
+    /// Number of immediate bytes this operand reads, 0 for registers and conditions
+    pub bytes: u8,
+    /// False if the operand is dereferenced, e.g. (HL)
+    pub immediate: bool,
+    /// HL+ and SP+
+    pub increment: bool,
+    /// HL-
+    pub decrement: bool
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FlagEffect
+{
+    Unaffected,
+    Reset,
+    Set,
+    Affected
+}
+
+include!(concat!(env!("OUT_DIR"), "/opcodes.rs"));
+
+impl Opcode
+{
+    /// Bitmask (in F register layout) of the flags this instruction may change
+    pub fn affected_mask(&self) -> u8
+    {
+        self.flag_mask(| effect | effect != FlagEffect::Unaffected)
+    }
+
+    /// Bitmask (in F register layout) of the flags this instruction always sets
+    pub fn set_mask(&self) -> u8
+    {
+        self.flag_mask(| effect | effect == FlagEffect::Set)
+    }
+
+    /// Bitmask (in F register layout) of the flags this instruction always clears
+    pub fn reset_mask(&self) -> u8
+    {
+        self.flag_mask(| effect | effect == FlagEffect::Reset)
+    }
+
+    fn flag_mask(&self, predicate: fn(FlagEffect) -> bool) -> u8
+    {
+        self.flags
+            .iter()
+            .enumerate()
+            .filter(| (_, effect) | predicate(**effect))
+            .fold(0, | mask, (i, _) | mask | (0x80 >> i))
+    }
+}
+
+/// Looks up an unprefixed opcode
+pub fn lookup(opcode: u8) -> &'static Opcode
+{
+    &UNPREFIXED[opcode as usize]
+}
+
+/// Looks up the second byte of a 0xCB-prefixed instruction
+pub fn lookup_prefixed(ext_opcode: u8) -> &'static Opcode
+{
+    &CB_PREFIXED[ext_opcode as usize]
+}
+
+/// Total length in bytes of the instruction starting with the given opcode, including the prefixed opcode for 0xCB
+pub fn instruction_len(opcode: u8) -> u8
+{
+    match opcode
+    {
+        0xCB => CB_PREFIXED[0].bytes, // Every prefixed instruction is the same length
+        _ => UNPREFIXED[opcode as usize].bytes
+    }
+}
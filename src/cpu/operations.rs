@@ -6,13 +6,39 @@ pub fn inst_len1(cpu: &mut Cpu, opcode: u8) -> fn(&mut Cpu, u8) -> Option<u8>
 {
     match opcode
     {
-        0x00 => return | cpu, opcode | nop(cpu, opcode),
-        0xAF => return | cpu, opcode | xor_a(cpu, opcode),
-        0xF3 => return | cpu, opcode | di(cpu, opcode),
-        0x47 => return | cpu, opcode | ld_b_a(cpu, opcode),
-        0x2F => return | cpu, opcode | cpl(cpu, opcode),
+        0x00 => | cpu, opcode | nop(cpu, opcode),
+        0x02 | 0x12 | 0x22 | 0x32 => | cpu, opcode | ld_indirect_a(cpu, opcode),
+        0x0A | 0x1A | 0x2A | 0x3A => | cpu, opcode | ld_a_indirect(cpu, opcode),
+        0x03 | 0x13 | 0x23 | 0x33 => | cpu, opcode | inc_r16(cpu, opcode),
+        0x0B | 0x1B | 0x2B | 0x3B => | cpu, opcode | dec_r16(cpu, opcode),
+        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x34 | 0x3C => | cpu, opcode | inc_r8(cpu, opcode),
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x35 | 0x3D => | cpu, opcode | dec_r8(cpu, opcode),
+        0x07 => | cpu, opcode | rlca(cpu, opcode),
+        0x0F => | cpu, opcode | rrca(cpu, opcode),
+        0x17 => | cpu, opcode | rla(cpu, opcode),
+        0x1F => | cpu, opcode | rra(cpu, opcode),
+        0x09 | 0x19 | 0x29 | 0x39 => | cpu, opcode | add_hl_r16(cpu, opcode),
+        0x27 => | cpu, opcode | daa(cpu, opcode),
+        0x2F => | cpu, opcode | cpl(cpu, opcode),
+        0x37 => | cpu, opcode | scf(cpu, opcode),
+        0x3F => | cpu, opcode | ccf(cpu, opcode),
+        0x76 => | cpu, opcode | halt(cpu, opcode),
+        0x40..=0x7F => | cpu, opcode | ld_r8_r8(cpu, opcode),
+        0x80..=0xBF => | cpu, opcode | alu_a_r8(cpu, opcode),
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => | cpu, opcode | ret_cc(cpu, opcode),
+        0xC9 => | cpu, opcode | ret(cpu, opcode),
+        0xD9 => | cpu, opcode | reti(cpu, opcode),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => | cpu, opcode | pop(cpu, opcode),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => | cpu, opcode | push(cpu, opcode),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => | cpu, opcode | rst(cpu, opcode),
+        0xE2 => | cpu, opcode | ld_c_a(cpu, opcode),
+        0xF2 => | cpu, opcode | ld_a_c(cpu, opcode),
+        0xE9 => | cpu, opcode | jp_hl(cpu, opcode),
+        0xF9 => | cpu, opcode | ld_sp_hl(cpu, opcode),
+        0xF3 => | cpu, opcode | di(cpu, opcode),
+        0xFB => | cpu, opcode | ei(cpu, opcode),
         _ => {
-            return | cpu, opcode | bad_opcode1(cpu, opcode);
+            | cpu, opcode | bad_opcode1(cpu, opcode)
         }
     }
 }
@@ -21,17 +47,17 @@ pub fn inst_len2(cpu: &mut Cpu, opcode: u8, arg: u8) -> fn(&mut Cpu, u8, u8) ->
 {
     match opcode
     {
-        0xFE => return | cpu, opcode, arg | cp_a(cpu, opcode, arg),
-        0x28 => return | cpu, opcode, arg | jr_z(cpu, opcode, arg),
-        0x18 => return | cpu, opcode, arg | jr(cpu, opcode, arg),
-        0x20 => return | cpu, opcode, arg | jr_nz(cpu, opcode, arg),
-        0xE0 => return | cpu, opcode, arg | ld_a8_a(cpu, opcode, arg),
-        0x3E => return | cpu, opcode, arg | ld_a_u8(cpu, opcode, arg),
-        0xE6 => return | cpu, opcode, arg | and_u8(cpu, opcode, arg),
-        0xF0 => return | cpu, opcode, arg | ld_a_a(cpu, opcode, arg),
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => | cpu, opcode, arg | ld_r8_u8(cpu, opcode, arg),
+        0x18 => | cpu, opcode, arg | jr(cpu, opcode, arg),
+        0x20 | 0x28 | 0x30 | 0x38 => | cpu, opcode, arg | jr_cc(cpu, opcode, arg),
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => | cpu, opcode, arg | alu_a_u8(cpu, opcode, arg),
+        0xE0 => | cpu, opcode, arg | ld_a8_a(cpu, opcode, arg),
+        0xF0 => | cpu, opcode, arg | ld_a_a8(cpu, opcode, arg),
+        0xE8 => | cpu, opcode, arg | add_sp_i8(cpu, opcode, arg),
+        0xF8 => | cpu, opcode, arg | ld_hl_sp_i8(cpu, opcode, arg),
         0xCB => match_prefixed_opcode(cpu, 0xCB, arg),
         _ => {
-            return | cpu, opcode, arg | bad_opcode2(cpu, opcode, arg);
+            | cpu, opcode, arg | bad_opcode2(cpu, opcode, arg)
         }
     }
 }
@@ -40,15 +66,106 @@ pub fn inst_len3(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> fn(&mut Cpu, u
 {
     match opcode
     {
-        0xC3 => return | cpu, opcode, low, high | jp(cpu, opcode, low, high),
-        0xEA => return | cpu, opcode, low, high | ld_a(cpu, opcode, low, high),
-        0xCD => return | cpu, opcode, low, high | call(cpu, opcode, low, high),
+        0x01 | 0x11 | 0x21 | 0x31 => | cpu, opcode, low, high | ld_r16_u16(cpu, opcode, low, high),
+        0x08 => | cpu, opcode, low, high | ld_a16_sp(cpu, opcode, low, high),
+        0xC3 => | cpu, opcode, low, high | jp(cpu, opcode, low, high),
+        0xC2 | 0xCA | 0xD2 | 0xDA => | cpu, opcode, low, high | jp_cc(cpu, opcode, low, high),
+        0xCD => | cpu, opcode, low, high | call(cpu, opcode, low, high),
+        0xC4 | 0xCC | 0xD4 | 0xDC => | cpu, opcode, low, high | call_cc(cpu, opcode, low, high),
+        0xEA => | cpu, opcode, low, high | ld_a16_a(cpu, opcode, low, high),
+        0xFA => | cpu, opcode, low, high | ld_a_a16(cpu, opcode, low, high),
         _ => {
-            return | cpu, opcode, low, high | bad_opcode3(cpu, opcode, low, high);
+            | cpu, opcode, low, high | bad_opcode3(cpu, opcode, low, high)
         }
     }
 }
 
+// Operand decoding
+
+/// Reads an 8-bit operand by its 3-bit index in the opcode: B, C, D, E, H, L, (HL), A
+fn read_r8(cpu: &mut Cpu, index: u8) -> u8
+{
+    match index & 0x07
+    {
+        0 => cpu.get_register(Register::B),
+        1 => cpu.get_register(Register::C),
+        2 => cpu.get_register(Register::D),
+        3 => cpu.get_register(Register::E),
+        4 => cpu.get_register(Register::H),
+        5 => cpu.get_register(Register::L),
+        6 => cpu.read_byte(cpu.registers.hl),
+        _ => cpu.get_register(Register::A),
+    }
+}
+
+/// Writes an 8-bit operand by its 3-bit index in the opcode: B, C, D, E, H, L, (HL), A
+fn write_r8(cpu: &mut Cpu, index: u8, value: u8)
+{
+    match index & 0x07
+    {
+        0 => cpu.set_register(Register::B, value),
+        1 => cpu.set_register(Register::C, value),
+        2 => cpu.set_register(Register::D, value),
+        3 => cpu.set_register(Register::E, value),
+        4 => cpu.set_register(Register::H, value),
+        5 => cpu.set_register(Register::L, value),
+        6 => cpu.write_byte(cpu.registers.hl, value),
+        _ => cpu.set_register(Register::A, value),
+    }
+}
+
+/// Reads a 16-bit register pair by its 2-bit index in the opcode: BC, DE, HL, SP
+fn read_r16(cpu: &Cpu, index: u8) -> u16
+{
+    match index & 0x03
+    {
+        0 => cpu.registers.bc,
+        1 => cpu.registers.de,
+        2 => cpu.registers.hl,
+        _ => cpu.registers.sp,
+    }
+}
+
+/// Writes a 16-bit register pair by its 2-bit index in the opcode: BC, DE, HL, SP
+fn write_r16(cpu: &mut Cpu, index: u8, value: u16)
+{
+    match index & 0x03
+    {
+        0 => cpu.registers.bc = value,
+        1 => cpu.registers.de = value,
+        2 => cpu.registers.hl = value,
+        _ => cpu.registers.sp = value,
+    }
+}
+
+/// Evaluates a branch condition by its 2-bit index in the opcode: NZ, Z, NC, C
+fn condition(cpu: &Cpu, opcode: u8) -> bool
+{
+    match (opcode >> 3) & 0x03
+    {
+        0 => !cpu.get_flag(Flag::Z),
+        1 => cpu.get_flag(Flag::Z),
+        2 => !cpu.get_flag(Flag::C),
+        _ => cpu.get_flag(Flag::C),
+    }
+}
+
+/// Runs one of the eight accumulator operations by its 3-bit index in the opcode: ADD, ADC, SUB, SBC, AND, XOR, OR, CP
+fn alu(cpu: &mut Cpu, operation: u8, value: u8)
+{
+    match operation & 0x07
+    {
+        0 => cpu.alu_add(value, false),
+        1 => cpu.alu_add(value, true),
+        2 => cpu.alu_sub(value, false, true),
+        3 => cpu.alu_sub(value, true, true),
+        4 => cpu.alu_and(value),
+        5 => cpu.alu_xor(value),
+        6 => cpu.alu_or(value),
+        _ => cpu.alu_sub(value, false, false),
+    }
+}
+
 // The grand table
 
 fn bad_opcode1(cpu: &mut Cpu, opcode: u8) -> Option<u8>
@@ -73,163 +190,855 @@ fn nop(cpu: &mut Cpu, opcode: u8) -> Option<u8>
     None
 }
 
-/// 0x18 JR
-fn jr(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x02, 0x12, 0x22, 0x32 LD (BC)/(DE)/(HL+)/(HL-), A
+fn ld_indirect_a(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    cpu.registers.pc += arg as u16;
+    let address = indirect_address(cpu, opcode);
+    cpu.write_byte(address, cpu.get_register(Register::A));
 
     None
 }
 
-fn jp(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+/// 0x0A, 0x1A, 0x2A, 0x3A LD A, (BC)/(DE)/(HL+)/(HL-)
+fn ld_a_indirect(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    cpu.registers.pc = ((low as u16) | ((high as u16) << 8)) - 1;
+    let address = indirect_address(cpu, opcode);
+    let value = cpu.read_byte(address);
+    cpu.set_register(Register::A, value);
 
     None
 }
 
-fn xor_a(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+/// Resolves the address of the (BC)/(DE)/(HL+)/(HL-) operand, applying the post-increment or post-decrement to HL
+fn indirect_address(cpu: &mut Cpu, opcode: u8) -> u16
 {
-    cpu.registers.af ^= cpu.registers.af & 0xFF00;
+    match (opcode >> 4) & 0x03
+    {
+        0 => cpu.registers.bc,
+        1 => cpu.registers.de,
+        2 => {
+            let hl = cpu.registers.hl;
+            cpu.registers.hl = hl.wrapping_add(1);
+            hl
+        },
+        _ => {
+            let hl = cpu.registers.hl;
+            cpu.registers.hl = hl.wrapping_sub(1);
+            hl
+        }
+    }
+}
 
-    cpu.set_multi_flags(
-        (cpu.registers.af & 0xFF00 == 0) as i8, 
-        0, 
-        0, 
-        0
-    );
+/// 0x01, 0x11, 0x21, 0x31 LD r16, u16
+fn ld_r16_u16(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+{
+    write_r16(cpu, opcode >> 4, ((high as u16) << 8) | low as u16);
 
     None
 }
 
-/// Take the logical AND of register A and the given immediate, and store the result in A
-/// Sets Z if A == 0, sets N, H, and C as 0, 1, 0 respectively.
-fn and_u8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x08 LD (u16), SP
+fn ld_a16_sp(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
 {
-    cpu.registers.af &= ((arg as u16) << 8) | 0x00FF;
+    let address = ((high as u16) << 8) | low as u16;
 
-    cpu.set_multi_flags(
-        (cpu.registers.af >> 8 == 0) as i8, 
-        0, 
-        1, 
-        0
-    );
+    cpu.write_byte(address, cpu.registers.sp as u8);
+    cpu.write_byte(address.wrapping_add(1), (cpu.registers.sp >> 8) as u8);
 
     None
 }
 
-fn ld_a(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+/// 0x03, 0x13, 0x23, 0x33 INC r16. Affects no flags.
+fn inc_r16(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    // TODO For now treat all memory as read/write capable, this is bad.
-    cpu.memory[(((high as u16) << 8) | low as u16) as usize] = (cpu.registers.af >> 8) as u8;
+    let value = read_r16(cpu, opcode >> 4).wrapping_add(1);
+    write_r16(cpu, opcode >> 4, value);
 
     None
 }
 
-fn ld_a8_a(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x0B, 0x1B, 0x2B, 0x3B DEC r16. Affects no flags.
+fn dec_r16(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    cpu.memory[(0xFF00 | arg as u16) as usize] = (cpu.registers.af >> 8) as u8;
+    let value = read_r16(cpu, opcode >> 4).wrapping_sub(1);
+    write_r16(cpu, opcode >> 4, value);
 
     None
 }
 
-fn ld_a_u8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x04, 0x0C, ..., 0x3C INC r8/(HL)
+fn inc_r8(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    cpu.registers.af |= (cpu.registers.af & 0x00FF) | ((arg as u16) << 8); 
+    let value = read_r8(cpu, opcode >> 3);
+    let result = cpu.alu_inc(value);
+    write_r8(cpu, opcode >> 3, result);
 
     None
 }
 
-fn ld_a_a(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x05, 0x0D, ..., 0x3D DEC r8/(HL)
+fn dec_r8(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    cpu.registers.af = ((cpu.memory[((arg as u16) << 8 | 0x00FF) as usize] as u16) << 8) | (cpu.registers.af & 0x00FF);
+    let value = read_r8(cpu, opcode >> 3);
+    let result = cpu.alu_dec(value);
+    write_r8(cpu, opcode >> 3, result);
 
     None
 }
 
-fn ld_b_a(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+/// 0x06, 0x0E, ..., 0x3E LD r8/(HL), u8
+fn ld_r8_u8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
 {
-    cpu.load_inter_register(Register::B, Register::A);
+    write_r8(cpu, opcode >> 3, arg);
 
     None
 }
 
-fn di(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+/// 0x07 RLCA. Unlike the prefixed RLC A, Z is always cleared.
+fn rlca(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let result = cpu.alu_rotate_left(cpu.get_register(Register::A), false);
+    cpu.set_register(Register::A, result);
+    cpu.clear_flag(Flag::Z);
+
+    None
+}
+
+/// 0x0F RRCA. Unlike the prefixed RRC A, Z is always cleared.
+fn rrca(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let result = cpu.alu_rotate_right(cpu.get_register(Register::A), false);
+    cpu.set_register(Register::A, result);
+    cpu.clear_flag(Flag::Z);
+
+    None
+}
+
+/// 0x17 RLA. Unlike the prefixed RL A, Z is always cleared.
+fn rla(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let result = cpu.alu_rotate_left(cpu.get_register(Register::A), true);
+    cpu.set_register(Register::A, result);
+    cpu.clear_flag(Flag::Z);
+
+    None
+}
+
+/// 0x1F RRA. Unlike the prefixed RR A, Z is always cleared.
+fn rra(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    // TODO This
+    let result = cpu.alu_rotate_right(cpu.get_register(Register::A), true);
+    cpu.set_register(Register::A, result);
+    cpu.clear_flag(Flag::Z);
+
+    None
+}
+
+/// 0x09, 0x19, 0x29, 0x39 ADD HL, r16
+fn add_hl_r16(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.alu_add_hl(read_r16(cpu, opcode >> 4));
+
+    None
+}
+
+/// 0x18 JR
+fn jr(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    // The offset is signed, and relative to the address of the next instruction
+    cpu.registers.pc = cpu.registers.pc.wrapping_add(arg as i8 as u16);
+
+    None
+}
+
+/// 0x20, 0x28, 0x30, 0x38 JR NZ/Z/NC/C
+fn jr_cc(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    cpu.branch_taken = condition(cpu, opcode);
+
+    if cpu.branch_taken
+    {
+        cpu.registers.pc = cpu.registers.pc.wrapping_add(arg as i8 as u16);
+    }
+
+    None
+}
+
+/// 0x27 DAA
+/// Adjusts A back into binary-coded decimal after an addition or subtraction, using N, H and C to tell which happened.
+fn daa(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let mut a = cpu.get_register(Register::A);
+    let mut carry = cpu.get_flag(Flag::C);
+
+    if !cpu.get_flag(Flag::N)
+    {
+        if carry || a > 0x99
+        {
+            a = a.wrapping_add(0x60);
+            carry = true;
+        }
+
+        if cpu.get_flag(Flag::H) || (a & 0x0F) > 0x09
+        {
+            a = a.wrapping_add(0x06);
+        }
+    } else {
+        if carry
+        {
+            a = a.wrapping_sub(0x60);
+        }
+
+        if cpu.get_flag(Flag::H)
+        {
+            a = a.wrapping_sub(0x06);
+        }
+    }
+
+    cpu.set_register(Register::A, a);
+    cpu.set_multi_flags((a == 0) as i8, -1, 0, carry as i8);
+
     None
 }
 
+/// 0x2F CPL
 fn cpl(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
     cpu.registers.af = (!((cpu.registers.af >> 8) as u8) as u16) << 8 | (cpu.registers.af & 0x00FF);
+    cpu.set_multi_flags(-1, 1, 1, -1);
 
     None
 }
 
-fn cp_a(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x37 SCF
+fn scf(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    // TODO Run operation once and store the result
+    cpu.set_multi_flags(-1, 0, 0, 1);
 
-    cpu.set_multi_flags(
-        ((cpu.registers.af >> 8) as u8 == arg) as i8, 
-        1, 
-        (((cpu.registers.af >> 8) as u8 == arg) && (((cpu.registers.af >> 8) as u8) == 0x0F)) as i8, 
-        (arg >= ((cpu.registers.af >> 8) as u8)) as i8
-    );
+    None
+}
+
+/// 0x3F CCF
+fn ccf(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let carry = cpu.get_flag(Flag::C);
+    cpu.set_multi_flags(-1, 0, 0, !carry as i8);
 
     None
 }
 
-fn jr_z(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x76 HALT
+/// Stops the CPU until an interrupt is pending. If IME is off and one is already pending, HALT doesn't stop at all,
+/// and instead triggers the HALT bug.
+fn halt(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    if cpu.get_flag(Flag::Z)
+    match (cpu.ime, cpu.bus.interrupts.pending())
     {
-        cpu.registers.pc += arg as u16;
+        (false, pending) if pending != 0 => cpu.halt_bug = true,
+        _ => cpu.halted = true
     }
 
     None
 }
 
-fn jr_nz(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+/// 0x40..=0x7F LD r8/(HL), r8/(HL), excluding 0x76 HALT
+fn ld_r8_r8(cpu: &mut Cpu, opcode: u8) -> Option<u8>
 {
-    if !cpu.get_flag(Flag::Z)
+    let value = read_r8(cpu, opcode);
+    write_r8(cpu, opcode >> 3, value);
+
+    None
+}
+
+/// 0x80..=0xBF ADD/ADC/SUB/SBC/AND/XOR/OR/CP A, r8/(HL)
+fn alu_a_r8(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let value = read_r8(cpu, opcode);
+    alu(cpu, opcode >> 3, value);
+
+    None
+}
+
+/// 0xC6, 0xCE, ..., 0xFE ADD/ADC/SUB/SBC/AND/XOR/OR/CP A, u8
+fn alu_a_u8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    alu(cpu, opcode >> 3, arg);
+
+    None
+}
+
+/// 0xC3 JP u16
+fn jp(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+{
+    cpu.registers.pc = (low as u16) | ((high as u16) << 8);
+
+    None
+}
+
+/// 0xC2, 0xCA, 0xD2, 0xDA JP NZ/Z/NC/C, u16
+fn jp_cc(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+{
+    cpu.branch_taken = condition(cpu, opcode);
+
+    if cpu.branch_taken
     {
-        cpu.registers.pc += arg as u16;
+        cpu.registers.pc = (low as u16) | ((high as u16) << 8);
     }
 
     None
 }
 
+/// 0xE9 JP HL
+fn jp_hl(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.registers.pc = cpu.registers.hl;
+
+    None
+}
+
+/// 0xCD CALL u16
 fn call(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
 {
-    cpu.push_stack((cpu.registers.pc >> 8) as u8);
-    cpu.push_stack(cpu.registers.pc as u8);
+    cpu.push_stack_u16(cpu.registers.pc);
+
+    cpu.registers.pc = ((high as u16) << 8) | low as u16;
+
+    None
+}
+
+/// 0xC4, 0xCC, 0xD4, 0xDC CALL NZ/Z/NC/C, u16
+fn call_cc(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+{
+    cpu.branch_taken = condition(cpu, opcode);
+
+    if cpu.branch_taken
+    {
+        call(cpu, opcode, low, high);
+    }
+
+    None
+}
+
+/// 0xC9 RET
+fn ret(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.registers.pc = cpu.pop_stack_u16();
+
+    None
+}
+
+/// 0xC0, 0xC8, 0xD0, 0xD8 RET NZ/Z/NC/C
+fn ret_cc(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.branch_taken = condition(cpu, opcode);
+
+    if cpu.branch_taken
+    {
+        cpu.registers.pc = cpu.pop_stack_u16();
+    }
+
+    None
+}
+
+/// 0xD9 RETI. Unlike EI, interrupts are enabled immediately.
+fn reti(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.registers.pc = cpu.pop_stack_u16();
+    cpu.ime = true;
+    cpu.ime_delay = 0;
+
+    None
+}
+
+/// 0xC7, 0xCF, ..., 0xFF RST. The target vector is encoded in bits 3-5 of the opcode.
+fn rst(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.push_stack_u16(cpu.registers.pc);
+    cpu.registers.pc = (opcode & 0x38) as u16;
+
+    None
+}
+
+/// 0xC1, 0xD1, 0xE1, 0xF1 POP BC/DE/HL/AF
+fn pop(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let value = cpu.pop_stack_u16();
+
+    match (opcode >> 4) & 0x03
+    {
+        3 => cpu.registers.af = value & 0xFFF0, // Low nibble of F is always 0
+        index => write_r16(cpu, index, value)
+    }
+
+    None
+}
+
+/// 0xC5, 0xD5, 0xE5, 0xF5 PUSH BC/DE/HL/AF
+fn push(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let value = match (opcode >> 4) & 0x03
+    {
+        3 => cpu.registers.af,
+        index => read_r16(cpu, index)
+    };
+
+    cpu.push_stack_u16(value);
+
+    None
+}
+
+/// 0xE0 LD ($FF00 + u8), A
+fn ld_a8_a(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    cpu.write_byte(0xFF00 | arg as u16, cpu.get_register(Register::A));
+
+    None
+}
+
+/// 0xF0 LD A, ($FF00 + u8)
+fn ld_a_a8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    let value = cpu.read_byte(0xFF00 | arg as u16);
+    cpu.set_register(Register::A, value);
+
+    None
+}
+
+/// 0xE2 LD ($FF00 + C), A
+fn ld_c_a(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.write_byte(0xFF00 | cpu.get_register(Register::C) as u16, cpu.get_register(Register::A));
+
+    None
+}
+
+/// 0xF2 LD A, ($FF00 + C)
+fn ld_a_c(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    let value = cpu.read_byte(0xFF00 | cpu.get_register(Register::C) as u16);
+    cpu.set_register(Register::A, value);
+
+    None
+}
+
+/// 0xEA LD (u16), A
+fn ld_a16_a(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+{
+    cpu.write_byte(((high as u16) << 8) | low as u16, cpu.get_register(Register::A));
+
+    None
+}
+
+/// 0xFA LD A, (u16)
+fn ld_a_a16(cpu: &mut Cpu, opcode: u8, low: u8, high: u8) -> Option<u8>
+{
+    let value = cpu.read_byte(((high as u16) << 8) | low as u16);
+    cpu.set_register(Register::A, value);
+
+    None
+}
+
+/// 0xE8 ADD SP, i8
+fn add_sp_i8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    cpu.registers.sp = cpu.alu_sp_offset(arg);
+
+    None
+}
+
+/// 0xF8 LD HL, SP + i8
+fn ld_hl_sp_i8(cpu: &mut Cpu, opcode: u8, arg: u8) -> Option<u8>
+{
+    cpu.registers.hl = cpu.alu_sp_offset(arg);
+
+    None
+}
+
+/// 0xF9 LD SP, HL
+fn ld_sp_hl(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.registers.sp = cpu.registers.hl;
+
+    None
+}
+
+/// 0xF3 DI. Also cancels an EI that hasn't taken effect yet.
+fn di(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    cpu.ime = false;
+    cpu.ime_delay = 0;
+
+    None
+}
 
-    cpu.registers.pc = (((high as u16) << 8) | low as u16) - 1;
+/// 0xFB EI. IME is set after the following instruction, so `EI; RET` returns before any interrupt is serviced.
+fn ei(cpu: &mut Cpu, opcode: u8) -> Option<u8>
+{
+    if !cpu.ime
+    {
+        cpu.ime_delay = 2; // Counts down once for EI itself, then once for the next instruction
+    }
 
     None
 }
 
 // Prefixed opcodes (0xCB__)
+// The low 3 bits of the extended opcode select the operand (B, C, D, E, H, L, (HL), A), and bits 3-5 select the operation or bit index.
 
 fn match_prefixed_opcode(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> fn (&mut Cpu, u8, u8) -> Option<u8>
 {
     match ext_opcode
     {
-        0x87 => | cpu, prefix, ext_opcode | cb_rst_0_a(cpu, prefix, ext_opcode),
-        _ => | cpu, prefix, ext_opcode | bad_prefixed_opcode(cpu, prefix, ext_opcode),
+        0x00..=0x3F => | cpu, prefix, ext_opcode | cb_shift(cpu, prefix, ext_opcode),
+        0x40..=0x7F => | cpu, prefix, ext_opcode | cb_bit(cpu, prefix, ext_opcode),
+        0x80..=0xBF => | cpu, prefix, ext_opcode | cb_res(cpu, prefix, ext_opcode),
+        0xC0..=0xFF => | cpu, prefix, ext_opcode | cb_set(cpu, prefix, ext_opcode),
     }
 }
 
-fn bad_prefixed_opcode(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> Option<u8>
+/// 0xCB00..=0xCB3F RLC/RRC/RL/RR/SLA/SRA/SWAP/SRL r8/(HL)
+fn cb_shift(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> Option<u8>
 {
-    Some(6)
+    let value = read_r8(cpu, ext_opcode);
+
+    let result = match (ext_opcode >> 3) & 0x07
+    {
+        0 => cpu.alu_rotate_left(value, false),
+        1 => cpu.alu_rotate_right(value, false),
+        2 => cpu.alu_rotate_left(value, true),
+        3 => cpu.alu_rotate_right(value, true),
+        4 => cpu.alu_shift_left(value),
+        5 => cpu.alu_shift_right(value, true),
+        6 => cpu.alu_swap(value),
+        _ => cpu.alu_shift_right(value, false),
+    };
+
+    write_r8(cpu, ext_opcode, result);
+
+    None
 }
 
-fn cb_rst_0_a(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> Option<u8>
+/// 0xCB40..=0xCB7F BIT n, r8/(HL)
+/// Sets Z if the bit is clear, clears N, sets H, and preserves C.
+fn cb_bit(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> Option<u8>
 {
-    cpu.registers.af &= 0b1111_1110_1111_1111;
+    let value = read_r8(cpu, ext_opcode);
+    let bit = (ext_opcode >> 3) & 0x07;
+
+    cpu.set_multi_flags((value & (1 << bit) == 0) as i8, 0, 1, -1);
 
     None
-}
\ No newline at end of file
+}
+
+/// 0xCB80..=0xCBBF RES n, r8/(HL). Affects no flags.
+fn cb_res(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> Option<u8>
+{
+    let value = read_r8(cpu, ext_opcode);
+    let bit = (ext_opcode >> 3) & 0x07;
+
+    write_r8(cpu, ext_opcode, value & !(1 << bit));
+
+    None
+}
+
+/// 0xCBC0..=0xCBFF SET n, r8/(HL). Affects no flags.
+fn cb_set(cpu: &mut Cpu, prefix: u8, ext_opcode: u8) -> Option<u8>
+{
+    let value = read_r8(cpu, ext_opcode);
+    let bit = (ext_opcode >> 3) & 0x07;
+
+    write_r8(cpu, ext_opcode, value | (1 << bit));
+
+    None
+}
+
+#[cfg(test)]
+mod tests
+{
+    use crate::component::{bus::MemoryMapped, cpu::{Cpu, Flag, Register}, interrupt::Interrupt};
+
+    /// Where test programs are loaded, in WRAM
+    const PROGRAM: u16 = 0xC000;
+
+    /// A CPU with a program at PC and a stack in HRAM
+    fn cpu_with(program: &[u8]) -> Cpu
+    {
+        let mut cpu = Cpu::new();
+
+        for (i, byte) in program.iter().enumerate()
+        {
+            cpu.bus.write(PROGRAM + i as u16, *byte);
+        }
+
+        cpu.registers.pc = PROGRAM;
+        cpu.registers.sp = 0xFFFE;
+
+        cpu
+    }
+
+    /// Fetches and executes one instruction, returning its M-cycles
+    fn step(cpu: &mut Cpu) -> u8
+    {
+        let opcode = cpu.fetch();
+
+        cpu.execute(opcode).unwrap()
+    }
+
+    /// Runs the instructions in a program one after the other, from the given A and F, and returns A and F afterwards
+    fn run_a(program: &[u8], a: u8, flags: u8) -> (u8, u8)
+    {
+        let mut cpu = cpu_with(program);
+        cpu.registers.af = ((a as u16) << 8) | flags as u16;
+
+        while cpu.registers.pc < PROGRAM + program.len() as u16
+        {
+            step(&mut cpu);
+        }
+
+        (cpu.get_register(Register::A), cpu.get_register(Register::F))
+    }
+
+    #[test]
+    fn daa_after_add()
+    {
+        // ADD A, u8; DAA
+        assert_eq!(run_a(&[0xC6, 0x27, 0x27], 0x15, 0x00), (0x42, 0x00));
+        // Half carry: 0x09 + 0x08 = 0x11
+        assert_eq!(run_a(&[0xC6, 0x08, 0x27], 0x09, 0x00), (0x17, 0x00));
+        // Carry: 0x90 + 0x90 = 0x120
+        assert_eq!(run_a(&[0xC6, 0x90, 0x27], 0x90, 0x00), (0x80, 0x10));
+        // 0x50 + 0x50 = 0xA0 doesn't carry, but needs adjusting into one
+        assert_eq!(run_a(&[0xC6, 0x50, 0x27], 0x50, 0x00), (0x00, 0x90));
+    }
+
+    #[test]
+    fn daa_after_sub()
+    {
+        // SUB A, u8; DAA. N is kept.
+        assert_eq!(run_a(&[0xD6, 0x15, 0x27], 0x42, 0x00), (0x27, 0x40));
+        // Half and full borrow: 0x15 - 0x27 = 0xEE
+        assert_eq!(run_a(&[0xD6, 0x27, 0x27], 0x15, 0x00), (0x88, 0x50));
+        assert_eq!(run_a(&[0xD6, 0x42, 0x27], 0x42, 0x00), (0x00, 0xC0));
+    }
+
+    #[test]
+    fn accumulator_rotates_clear_z()
+    {
+        // RLCA, RRCA with A = 0 leave 0 but never set Z
+        assert_eq!(run_a(&[0x07], 0x00, 0x80), (0x00, 0x00));
+        assert_eq!(run_a(&[0x0F], 0x00, 0x80), (0x00, 0x00));
+        // RLA and RRA shifting the only set bit out into C
+        assert_eq!(run_a(&[0x17], 0x80, 0x00), (0x00, 0x10));
+        assert_eq!(run_a(&[0x1F], 0x01, 0x00), (0x00, 0x10));
+        // Through carry: the old C comes back in
+        assert_eq!(run_a(&[0x17], 0x40, 0x10), (0x81, 0x00));
+        assert_eq!(run_a(&[0x0F], 0x01, 0x00), (0x80, 0x10));
+    }
+
+    /// Runs a conditional branch with Z set or clear, and returns its M-cycles and where PC ended up
+    fn branch(program: &[u8], zero: bool) -> (u8, u16)
+    {
+        let mut cpu = cpu_with(program);
+        cpu.set_register(Register::F, (zero as u8) << Flag::Z as u8);
+        cpu.push_stack_u16(0x1234);
+
+        (step(&mut cpu), cpu.registers.pc)
+    }
+
+    #[test]
+    fn taken_branches_take_longer()
+    {
+        // JR Z, +5
+        assert_eq!(branch(&[0x28, 0x05], true), (3, PROGRAM + 7));
+        assert_eq!(branch(&[0x28, 0x05], false), (2, PROGRAM + 2));
+        // JP Z, $4000
+        assert_eq!(branch(&[0xCA, 0x00, 0x40], true), (4, 0x4000));
+        assert_eq!(branch(&[0xCA, 0x00, 0x40], false), (3, PROGRAM + 3));
+        // CALL Z, $4000
+        assert_eq!(branch(&[0xCC, 0x00, 0x40], true), (6, 0x4000));
+        assert_eq!(branch(&[0xCC, 0x00, 0x40], false), (3, PROGRAM + 3));
+        // RET Z
+        assert_eq!(branch(&[0xC8], true), (5, 0x1234));
+        assert_eq!(branch(&[0xC8], false), (2, PROGRAM + 1));
+    }
+
+    #[test]
+    fn unconditional_branches_take_the_taken_time()
+    {
+        assert_eq!(branch(&[0x18, 0xFE], false), (3, PROGRAM));
+        assert_eq!(branch(&[0xC3, 0x00, 0x40], false), (4, 0x4000));
+        assert_eq!(branch(&[0xCD, 0x00, 0x40], false), (6, 0x4000));
+        assert_eq!(branch(&[0xC9], false), (4, 0x1234));
+    }
+
+    /// Runs a prefixed instruction on B, from the given F, and returns B and F afterwards
+    fn run_cb(ext_opcode: u8, b: u8, flags: u8) -> (u8, u8)
+    {
+        let mut cpu = cpu_with(&[0xCB, ext_opcode]);
+        cpu.set_register(Register::B, b);
+        cpu.set_register(Register::F, flags);
+
+        step(&mut cpu);
+
+        (cpu.get_register(Register::B), cpu.get_register(Register::F))
+    }
+
+    #[test]
+    fn cb_rotates()
+    {
+        // RLC B: bit 7 into C and bit 0
+        assert_eq!(run_cb(0x00, 0x85, 0x00), (0x0B, 0x10));
+        assert_eq!(run_cb(0x00, 0x00, 0x70), (0x00, 0x80));
+        // RR B: C into bit 7, bit 0 into C
+        assert_eq!(run_cb(0x18, 0x01, 0x10), (0x80, 0x10));
+        assert_eq!(run_cb(0x18, 0x01, 0x00), (0x00, 0x90));
+    }
+
+    #[test]
+    fn cb_shifts()
+    {
+        // SRA B keeps bit 7
+        assert_eq!(run_cb(0x28, 0x81, 0x00), (0xC0, 0x10));
+        assert_eq!(run_cb(0x28, 0x01, 0x00), (0x00, 0x90));
+        // SRL B clears bit 7
+        assert_eq!(run_cb(0x38, 0x81, 0x00), (0x40, 0x10));
+        assert_eq!(run_cb(0x38, 0x01, 0x00), (0x00, 0x90));
+    }
+
+    #[test]
+    fn cb_swap()
+    {
+        // SWAP B clears N, H and C
+        assert_eq!(run_cb(0x30, 0xF1, 0x70), (0x1F, 0x00));
+        assert_eq!(run_cb(0x30, 0x00, 0x70), (0x00, 0x80));
+    }
+
+    /// Runs a prefixed instruction on (HL), from the given F, and returns its M-cycles, (HL) and F afterwards
+    fn run_cb_hl(ext_opcode: u8, value: u8, flags: u8) -> (u8, u8, u8)
+    {
+        let mut cpu = cpu_with(&[0xCB, ext_opcode]);
+        cpu.registers.hl = 0xD000;
+        cpu.bus.write(0xD000, value);
+        cpu.set_register(Register::F, flags);
+
+        let cycles = step(&mut cpu);
+
+        (cycles, cpu.bus.read(0xD000), cpu.get_register(Register::F))
+    }
+
+    #[test]
+    fn cb_bit_on_hl()
+    {
+        // BIT 7, (HL): Z from the bit, N cleared, H set, C kept, and (HL) only read
+        assert_eq!(run_cb_hl(0x7E, 0x80, 0x50), (3, 0x80, 0x30));
+        assert_eq!(run_cb_hl(0x7E, 0x7F, 0x00), (3, 0x7F, 0xA0));
+        // BIT 0, B on a register leaves it alone too
+        assert_eq!(run_cb(0x40, 0x01, 0x00), (0x01, 0x20));
+    }
+
+    #[test]
+    fn cb_res_and_set_on_hl()
+    {
+        // RES 0, (HL) and SET 7, (HL) write back, and leave flags alone
+        assert_eq!(run_cb_hl(0x86, 0xFF, 0xF0), (4, 0xFE, 0xF0));
+        assert_eq!(run_cb_hl(0xFE, 0x00, 0x00), (4, 0x80, 0x00));
+        assert_eq!(run_cb_hl(0xB6, 0x40, 0x00), (4, 0x00, 0x00));
+    }
+
+    /// A CPU with VBlank enabled and requested, so an interrupt is pending from the start
+    fn cpu_with_vblank(program: &[u8]) -> Cpu
+    {
+        let mut cpu = cpu_with(program);
+        cpu.bus.interrupts.enabled = 0x01;
+        cpu.bus.interrupts.requested = 0x01;
+
+        cpu
+    }
+
+    #[test]
+    fn ei_waits_one_instruction()
+    {
+        // EI; NOP; NOP
+        let mut cpu = cpu_with_vblank(&[0xFB, 0x00, 0x00]);
+
+        assert_eq!(cpu.handle_interrupts(), None);
+        step(&mut cpu);
+
+        // The instruction after EI still runs first
+        assert_eq!(cpu.handle_interrupts(), None);
+        step(&mut cpu);
+
+        assert_eq!(cpu.handle_interrupts(), Some(5));
+        assert_eq!(cpu.registers.pc, 0x0040);
+        assert_eq!(cpu.pop_stack_u16(), PROGRAM + 2);
+        assert!(!cpu.ime);
+    }
+
+    #[test]
+    fn di_cancels_pending_ei()
+    {
+        // EI; DI; NOP
+        let mut cpu = cpu_with_vblank(&[0xFB, 0xF3, 0x00]);
+
+        step(&mut cpu);
+        step(&mut cpu);
+        step(&mut cpu);
+
+        assert!(!cpu.ime);
+        assert_eq!(cpu.handle_interrupts(), None);
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice()
+    {
+        // HALT; INC A; NOP with IME off and an interrupt already pending
+        let mut cpu = cpu_with_vblank(&[0x76, 0x3C, 0x00]);
+        cpu.set_register(Register::A, 0x10);
+
+        step(&mut cpu);
+        assert!(!cpu.halted);
+
+        step(&mut cpu);
+        assert_eq!(cpu.registers.pc, PROGRAM + 1);
+
+        step(&mut cpu);
+        assert_eq!(cpu.registers.pc, PROGRAM + 2);
+        assert_eq!(cpu.get_register(Register::A), 0x12);
+    }
+
+    #[test]
+    fn halt_waits_for_an_interrupt_with_ime_off()
+    {
+        let mut cpu = cpu_with(&[0x76, 0x00]);
+        cpu.bus.interrupts.enabled = 0x04;
+        cpu.bus.interrupts.requested = 0x00;
+
+        step(&mut cpu);
+        assert!(cpu.halted);
+        assert_eq!(cpu.handle_interrupts(), None);
+        assert!(cpu.halted);
+
+        // Wakes without dispatching, and carries on after HALT
+        cpu.bus.interrupts.request(Interrupt::Timer);
+        assert_eq!(cpu.handle_interrupts(), None);
+        assert!(!cpu.halted);
+        assert_eq!(cpu.registers.pc, PROGRAM + 1);
+    }
+
+    #[test]
+    fn memory_accesses_step_the_timer()
+    {
+        // LDH A, (TIMA) twice, with TIMA counting every 4 M-cycles from a reset DIV
+        let mut cpu = cpu_with(&[0xF0, 0x05, 0xF0, 0x05]);
+        cpu.bus.write(0xFF04, 0x00);
+        cpu.bus.write(0xFF07, 0x05);
+
+        // Each read lands on the third M-cycle of its instruction, so only the second sees the increment on the fourth
+        step(&mut cpu);
+        assert_eq!(cpu.get_register(Register::A), 0x00);
+
+        step(&mut cpu);
+        assert_eq!(cpu.get_register(Register::A), 0x01);
+        assert_eq!(cpu.take_ticked(), 6);
+    }
+}
@@ -1,176 +1,304 @@
 #![allow(dead_code)]
 
-use std::num::Wrapping;
+use crate::{component::{apu::sink::AudioSink, cartridge::{header::{self, RamSize}, Cartridge}, cpu::{Cpu, Registers}, joypad::Buttons, ppu::{Ppu, PpuBackend, SCREEN_HEIGHT, SCREEN_WIDTH}}, cpu::opcodes, lookups};
 
-use crate::{component::{cartridge::Cartridge, cpu::Cpu}, lookups};
+/// How often dirty save RAM is flushed to disk, in T-cycles (roughly every second)
+const SAVE_INTERVAL: u64 = 4_194_304;
 
-/// Logo bytes
-const LOGO_DUMP: [u8; 48] = [0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D, 0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99, 0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E];
+/// Which hardware the emulated system claims to be
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Model
+{
+    Dmg,
+    /// Only changes what games can detect (the boot ROM's register values). CGB hardware isn't emulated.
+    Cgb
+}
+
+impl Model
+{
+    /// Size of this model's boot ROM
+    pub fn boot_rom_size(&self) -> usize
+    {
+        match self
+        {
+            Model::Dmg => 0x100,
+            Model::Cgb => 0x900
+        }
+    }
+}
 
 pub struct Gameboy
 {
-    pub cartridge: Option<Cartridge>,
-    cpu: Cpu
+    cpu: Cpu,
+    /// Master clock, in T-cycles since power on. Every other component is stepped against this.
+    cycles: u64,
+    /// Master clock value at the last save RAM flush
+    last_save: u64,
+    /// Receives the audio at the end of every frame, if anything is listening
+    audio_sink: Option<Box<dyn AudioSink>>,
+    /// Kept so a reset can rebuild the PPU the same way
+    ppu_backend: PpuBackend,
+    model: Model,
+    /// Kept so a reset can map it again
+    boot_rom: Option<Vec<u8>>
 }
 
 impl Gameboy
 {
-    pub fn construct() -> Self
+    pub fn construct(ppu_backend: PpuBackend, model: Model) -> Self
     {
-        Gameboy
+        let mut system = Gameboy
         {
-            cartridge: None,
-            cpu: Cpu::new()
+            cpu: Cpu::new(),
+            cycles: 0,
+            last_save: 0,
+            audio_sink: None,
+            ppu_backend,
+            model,
+            boot_rom: None
+        };
+
+        system.power_on();
+
+        system
+    }
+
+    /// Puts the CPU and PPU in their power-on state: at $0000 if there's a boot ROM to run, otherwise where the
+    /// model's boot ROM would have left them.
+    fn power_on(&mut self)
+    {
+        self.cpu.bus.ppu = Ppu::with_backend(self.ppu_backend);
+
+        match (&self.boot_rom, self.model)
+        {
+            (Some(boot_rom), _) => {
+                self.cpu.bus.boot_rom = Some(boot_rom.clone());
+                self.cpu.registers = Registers { af: 0, bc: 0, de: 0, hl: 0, pc: 0x0000, sp: 0 };
+            },
+            (None, Model::Dmg) => self.cpu.registers = Registers { af: 0x01B0, bc: 0x0013, de: 0x00D8, hl: 0x014D, pc: 0x0100, sp: 0xFFFE },
+            (None, Model::Cgb) => self.cpu.registers = Registers { af: 0x1180, bc: 0x0000, de: 0xFF56, hl: 0x000D, pc: 0x0100, sp: 0xFFFE }
         }
     }
 
-    pub fn insert_cartridge(&mut self, cart: Cartridge)
+    pub fn model(&self) -> Model
     {
-        self.cartridge = Some(cart);
+        self.model
+    }
+
+    /// Starts from a boot ROM at $0000 instead of at $0100, now and after every reset. Should be set before anything
+    /// runs. Fails if it's the wrong size for the model.
+    pub fn set_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<(), String>
+    {
+        if boot_rom.len() != self.model.boot_rom_size()
+        {
+            return Err(format!("Expected a {}B boot ROM for {:?}, found {}B", self.model.boot_rom_size(), self.model, boot_rom.len()));
+        }
+
+        self.boot_rom = Some(boot_rom);
+        self.power_on();
+
+        Ok(())
+    }
+
+    pub fn insert_cartridge(&mut self, mut cart: Cartridge)
+    {
+        cart.load_save_file();
+
+        self.cpu.bus.cartridge = Some(cart);
+    }
+
+    /// Writes battery-backed cartridge RAM out to its save file
+    pub fn save_battery(&mut self)
+    {
+        if let Some(cart) = &mut self.cpu.bus.cartridge
+        {
+            if let Err(err) = cart.write_save_file()
+            {
+                println!("Failed to write save data. Progress since the last save may be lost. (Technical error: {})", err);
+            }
+        }
     }
 
     pub fn read_cart_data(&self)
     {
-        match &self.cartridge
+        let header = match &self.cpu.bus.cartridge
         {
-            Some(_) => { },
+            Some(cart) => cart.header(),
             None => {
                 println!("Cannot read cartridge, as one is not inserted!");
                 return;
             }
-        }
+        };
 
-        let checksum: u8 = self.compute_checksum();
-        let cart_checksum = self.cartridge.as_ref().unwrap().rom[0x14D];
+        match header.header_checksum_valid
+        {
+            true => println!("Header checksum succeeded. (Expected: 0x{:0>2X})", header.header_checksum),
+            false => println!("Header checksum failed. This may mean the ROM is corrupt. (Expected: 0x{:0>2X}, given: 0x{:0>2X})", self.compute_checksum(), header.header_checksum)
+        }
 
-        if cart_checksum != checksum
+        match self.compute_dumb_checksum() == header.global_checksum
         {
-            println!("Header checksum failed. This may mean the ROM is corrupt. (Expected: 0x{:0>2X}, given: 0x{:0>2X})", cart_checksum, checksum);
-        } else {
-            println!("Header checksum succeeded. (Expected: 0x{:0>2X})", checksum);
+            true => println!("Global checksum succeeded."),
+            false => println!("Global checksum failed. This is usually safe to ignore.")
         }
 
-        let global_checksum: u16 = self.compute_dumb_checksum();
-        let cart_global_checksum: u16 = self.cartridge.as_ref().unwrap().rom[0x14F] as u16 | ((self.cartridge.as_ref().unwrap().rom[0x14E] as u16) << 8);
+        println!("\nCartridge title: {}", header.title);
 
-        if global_checksum != cart_global_checksum
+        match header.logo_valid
         {
-            println!("Global checksum failed. This is usually safe to ignore.");
-        } else {
-            println!("Global checksum succeeded.");
+            true => println!("Valid Nintendo logo found."),
+            false => println!("Invalid Nintendo logo. This may mean the ROM is corrupt, or it is an unofficial cartridge.")
         }
 
-        // Read off header bytes
+        let cart_type = header.cartridge_type;
+        println!("Cartridge meta: Type: {} | No. of ROM banks: {} | No. of RAM banks: {} | Battery backed: {} | Timer present: {}", cart_type.name(), cart_type.max_rom_banks(), cart_type.max_ram_banks(), cart_type.has_battery(), cart_type.has_timer());
 
-        // Title
-        let name: &str = &self.cart().rom[0x134..=0x143] // Take the bytes that hold the title,
-            .iter() // turn it into an iterator,
-            .map(| n | *n as char) // map the bytes to chars,
-            .collect::<String>(); // and collect it into a String
+        let actual = self.cart().rom.len();
 
-        println!("\nCartridge title: {}", name);
+        match header.rom_size.bytes()
+        {
+            Some(size) if size == actual => println!("ROM size: {}KiB", size / 1024),
+            Some(size) => println!("ROM size: {}KiB, but the file is {}B. This may be a bad dump, or a patch may have changed its size.", size / 1024, actual),
+            None => println!("ROM size: Unknown, the file is {}B", actual)
+        }
 
-        // Logo
-        let logo: [u8; 48] = self.cart().rom[0x104..=0x133].try_into().unwrap();
-        if logo != LOGO_DUMP
+        match header.ram_size
         {
-            println!("Invalid Nintendo logo. This may mean the ROM is corrupt, or it is an unofficial cartridge.");
-        } else {
-            println!("Valid Nintendo logo found.");
+            RamSize::Unknown(_) => println!("RAM size: Unknown"),
+            size => println!("RAM size: {}KiB", size.bytes() / 1024)
         }
+    }
+
+    pub fn compute_checksum(&self) -> u8
+    {
+        header::header_checksum(&self.cart().rom)
+    }
 
-        let cart_meta = lookups::mbc_type(self.cart().rom[0x147]);
-        println!("Cartridge meta: Type: {} | No. of ROM banks: {} | No. of RAM banks: {} | Battery backed: {} | Timer present: {}", cart_meta.0, cart_meta.1, cart_meta.2, cart_meta.3, cart_meta.4);
+    /// Computes the cartridge's global checksum. The gameboy doesn't actually do this.
+    pub fn compute_dumb_checksum(&self) -> u16
+    {
+        header::global_checksum(&self.cart().rom)
+    }
 
-        // ROM size
-        println!("ROM size: {}KiB", 32 << self.cart().rom[0x148]);
+    pub fn cart(&self) -> &Cartridge
+    {
+        self.cpu.bus.cartridge.as_ref().unwrap()
+    }
 
-        // RAM size
-        println!("RAM size: {}KiB", match self.cart().rom[0x149] {
-            0 => {
-                "0"
-            },
-            2 => {
-                "8"
-            },
-            3 => {
-                "32"
-            },
-            4 => {
-                "128"
-            },
-            5 => {
-                "64"
-            },
-            _ => {
-                "Unknown"
-            }
-        });
+    /// Master clock, in T-cycles since power on
+    pub fn cycles(&self) -> u64
+    {
+        self.cycles
     }
 
-    pub fn compute_checksum(&self) -> u8
+    /// Power cycles the system with the same cartridge inserted. Save RAM is written out first.
+    pub fn reset(&mut self)
     {
-        let mut x: Wrapping<u8> = Wrapping(0);
+        self.save_battery();
 
-        for i in self.cartridge.as_ref().unwrap().rom[0x134..=0x14C].iter()
-        {
-            x = x - Wrapping(*i) - Wrapping(1);
-        }
+        let cartridge = self.cpu.bus.cartridge.take();
+        let sample_rate = self.cpu.bus.apu.sample_rate();
 
-        x.0
+        self.cpu = Cpu::new();
+        self.power_on();
+        self.cpu.bus.apu.set_sample_rate(sample_rate);
+        self.cpu.bus.cartridge = cartridge;
+
+        self.cycles = 0;
+        self.last_save = 0;
     }
 
-    /// Computes the cartridge's global checksum. The gameboy doesn't actually do this.
-    pub fn compute_dumb_checksum(&self) -> u16
+    /// The last completed frame, as shade indices (0 = white, 3 = black)
+    pub fn framebuffer(&self) -> &[u8; SCREEN_WIDTH * SCREEN_HEIGHT]
     {
-        let mut x: Wrapping<u16> = Wrapping(0);
+        self.cpu.bus.ppu.framebuffer()
+    }
 
-        for (i, e) in self.cartridge.as_ref().unwrap().rom[0x0000..=0xFFFF].iter().enumerate()
-        {
-            if i == 0x14E || i == 0x14F
+    /// Runs until the PPU completes a frame. Returns the exit code and opcode if the CPU stops first.
+    pub fn run_frame(&mut self) -> Result<(), (u8, u8)>
+    {
+        loop {
+            if self.step(false)?
             {
-                continue;
+                return Ok(());
             }
-
-            x = x + Wrapping(*e as u16);
         }
+    }
 
-        x.0
+    /// Sets which buttons are held down. Buttons not in the set are released.
+    pub fn set_buttons(&mut self, buttons: Buttons)
+    {
+        self.cpu.bus.joypad.set_buttons(buttons, &mut self.cpu.bus.interrupts);
     }
 
-    pub fn cart(&self) -> &Cartridge
+    /// Sets the rate the APU produces samples at, to match the host's audio output
+    pub fn set_sample_rate(&mut self, sample_rate: u32)
     {
-        self.cartridge.as_ref().unwrap()
+        self.cpu.bus.apu.set_sample_rate(sample_rate);
     }
 
-    pub fn start_cart(&mut self, print_state: bool)
+    /// Takes the audio generated since the last call, as interleaved stereo samples
+    pub fn take_samples(&mut self) -> Vec<f32>
     {
-        // Init PC
-        // Begin execution at $0100
-        self.cpu.registers.pc = 0x00FF;
+        self.cpu.bus.apu.take_samples()
+    }
 
-        let mut opcode;
-        let mut len;
+    /// Sends audio to a sink from now on, at the sink's sample rate
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>)
+    {
+        self.set_sample_rate(sink.sample_rate());
+        self.audio_sink = Some(sink);
+    }
 
-        if print_state
+    /// Hands the frame's audio to the sink and applies its rate adjustment. Without a sink the audio is dropped.
+    fn flush_audio(&mut self)
+    {
+        let samples = self.take_samples();
+
+        if let Some(sink) = &mut self.audio_sink
         {
-            println!("----------< BEGIN READOUT >----------");
-            println!("ADDRESS:  | CACHE: INST   ARGL ARGH | REGISTERS: AF:   HHLL  BC:   HHLL  DE:   HHLL  HL:   HHLL | FLAGS:");
+            sink.push_samples(&samples);
+            self.cpu.bus.apu.set_rate_adjust(sink.rate_adjust());
         }
+    }
+
+    /// Advances the master clock, and every component with it, by a number of M-cycles
+    fn advance(&mut self, m_cycles: u8)
+    {
+        let t_cycles = m_cycles as u32 * 4;
+
+        // Memory accesses have already stepped the bus through their own cycles
+        let remaining = m_cycles.saturating_sub(self.cpu.take_ticked());
 
-        let exitcode = loop {
-            self.cpu.cache.clear();
-            self.cpu.registers.pc += 1;
+        self.cycles += t_cycles as u64;
+        self.cpu.bus.tick(remaining as u32 * 4);
 
-            opcode = self.cartridge.as_ref().unwrap().rom[self.cpu.registers.pc as usize];
-            len = lookups::instruction_len(&opcode);
+        if self.cycles - self.last_save >= SAVE_INTERVAL
+        {
+            self.last_save = self.cycles;
 
-            // Load bytes into cache
-            while self.cpu.cache.len() < (len - 1) as usize
+            if let Some(cart) = &self.cpu.bus.cartridge
             {
-                self.cpu.registers.pc += 1;
-                self.cpu.cache.push(self.cartridge.as_ref().unwrap().rom[self.cpu.registers.pc as usize]);
+                if cart.is_dirty()
+                {
+                    self.save_battery();
+                }
             }
+        }
+    }
+
+    /// Runs the CPU for one step: services an interrupt, idles for an M-cycle while halted, or executes an instruction.
+    /// Returns whether a frame was completed along the way, or the exit code and opcode if the CPU stopped.
+    fn step(&mut self, print_state: bool) -> Result<bool, (u8, u8)>
+    {
+        if let Some(m_cycles) = self.cpu.handle_interrupts()
+        {
+            self.advance(m_cycles);
+        } else if self.cpu.halted {
+            // Nothing runs while halted, but the rest of the system keeps going until an interrupt wakes the CPU
+            self.advance(1);
+        } else {
+            let opcode = self.cpu.fetch();
 
             if print_state
             {
@@ -179,15 +307,96 @@ impl Gameboy
 
             match self.cpu.execute(opcode)
             {
-                Some(code) => break code,
-                None => {
-                    continue;
+                Ok(m_cycles) => self.advance(m_cycles),
+                Err(code) => return Err((code, opcode))
+            }
+        }
+
+        let frame_done = self.cpu.bus.ppu.take_frame_ready();
+
+        if frame_done
+        {
+            self.flush_audio();
+        }
+
+        Ok(frame_done)
+    }
+
+    /// Runs the cartridge until the CPU stops, until `max_frames` frames have been drawn, or until the master clock
+    /// reaches `max_cycles`
+    pub fn start_cart(&mut self, print_state: bool, max_frames: Option<u64>, max_cycles: Option<u64>)
+    {
+        if print_state
+        {
+            println!("----------< BEGIN READOUT >----------");
+            println!("ADDRESS:  | CACHE: INSTRUCTION        ARGL ARGH | REGISTERS: AF:   HHLL  BC:   HHLL  DE:   HHLL  HL:   HHLL | FLAGS:");
+        }
+
+        let mut frames: u64 = 0;
+
+        let exit = loop {
+            match self.step(print_state)
+            {
+                Ok(true) => {
+                    frames += 1;
+
+                    if max_frames.is_some_and(| max | frames >= max)
+                    {
+                        break None;
+                    }
                 },
+                Ok(false) => { },
+                Err(exit) => break Some(exit)
+            }
+
+            if max_cycles.is_some_and(| max | self.cycles >= max)
+            {
+                break None;
             }
         };
-        
-        println!("----------<  END READOUT  >----------");
 
-        println!("Gameboy routine exited with exit code {} ({} | Opcode 0x{:0>2X} @ PC ${:0>4X}).", exitcode, lookups::exit_codes(exitcode), opcode, self.cpu.registers.pc - (len as u16 - 1));
+        if print_state
+        {
+            println!("----------<  END READOUT  >----------");
+        }
+
+        self.save_battery();
+
+        match exit
+        {
+            Some((exitcode, opcode)) => println!("Gameboy routine exited with exit code {} ({} | Opcode 0x{:0>2X} @ PC ${:0>4X}).", exitcode, lookups::exit_codes(exitcode), opcode, self.cpu.registers.pc.wrapping_sub(opcodes::instruction_len(opcode) as u16)),
+            None => println!("Gameboy routine stopped after {} frames ({} cycles).", frames, self.cycles)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn registers(system: &Gameboy) -> [u16; 6]
+    {
+        let registers = &system.cpu.registers;
+        [registers.af, registers.bc, registers.de, registers.hl, registers.sp, registers.pc]
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn dmg_starts_with_post_boot_registers()
+    {
+        let mut system = Gameboy::construct(PpuBackend::Scanline, Model::Dmg);
+        assert_eq!(registers(&system), [0x01B0, 0x0013, 0x00D8, 0x014D, 0xFFFE, 0x0100]);
+
+        system.cpu.registers.pc = 0x1234;
+        system.cpu.registers.af = 0;
+        system.reset();
+        assert_eq!(registers(&system), [0x01B0, 0x0013, 0x00D8, 0x014D, 0xFFFE, 0x0100]);
+    }
+
+    #[test]
+    fn cgb_starts_with_post_boot_registers()
+    {
+        let system = Gameboy::construct(PpuBackend::Scanline, Model::Cgb);
+        assert_eq!(registers(&system), [0x1180, 0x0000, 0xFF56, 0x000D, 0xFFFE, 0x0100]);
+    }
+}
@@ -0,0 +1,97 @@
+#![allow(dead_code)]
+
+use gilrs::{Axis, Button, Gilrs};
+
+use argentous_oxide::component::joypad::Buttons;
+
+/// How far a stick has to be pushed to count as a d-pad press
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Face buttons use the Xbox layout positions, so A is the bottom button and B the right one, as on a Gameboy
+const BUTTONMAP: [(Button, Buttons); 8] = [
+    (Button::South, Buttons::A),
+    (Button::East, Buttons::B),
+    (Button::Select, Buttons::SELECT),
+    (Button::Start, Buttons::START),
+    (Button::DPadRight, Buttons::RIGHT),
+    (Button::DPadLeft, Buttons::LEFT),
+    (Button::DPadUp, Buttons::UP),
+    (Button::DPadDown, Buttons::DOWN)
+];
+
+/// Every connected gamepad, merged into one set of buttons
+pub struct Gamepads
+{
+    /// None if gamepad support couldn't be initialised, in which case only the keyboard works
+    gilrs: Option<Gilrs>
+}
+
+impl Default for Gamepads
+{
+    fn default() -> Self
+    {
+        Gamepads::new()
+    }
+}
+
+impl Gamepads
+{
+    pub fn new() -> Self
+    {
+        let gilrs = match Gilrs::new()
+        {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                println!("Gamepads are unavailable, only the keyboard will work. (Technical error: {})", err);
+                None
+            }
+        };
+
+        Gamepads { gilrs }
+    }
+
+    /// Buttons held on any connected gamepad, with the left stick doubling as the d-pad
+    pub fn poll(&mut self) -> Buttons
+    {
+        let mut buttons = Buttons::empty();
+
+        let Some(gilrs) = &mut self.gilrs else
+        {
+            return buttons;
+        };
+
+        // Events have to be drained for gilrs to update gamepad state
+        while gilrs.next_event().is_some() { }
+
+        for (_, gamepad) in gilrs.gamepads()
+        {
+            for (button, mapped) in BUTTONMAP
+            {
+                if gamepad.is_pressed(button)
+                {
+                    buttons |= mapped;
+                }
+            }
+
+            let x = gamepad.value(Axis::LeftStickX);
+            let y = gamepad.value(Axis::LeftStickY);
+
+            let stick = [
+                (x > STICK_THRESHOLD, Buttons::RIGHT),
+                (x < -STICK_THRESHOLD, Buttons::LEFT),
+                (y > STICK_THRESHOLD, Buttons::UP),
+                (y < -STICK_THRESHOLD, Buttons::DOWN)
+            ];
+
+            for (pushed, direction) in stick
+            {
+                if pushed
+                {
+                    buttons |= direction;
+                }
+            }
+        }
+
+        buttons
+    }
+}
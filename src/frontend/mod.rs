@@ -0,0 +1,176 @@
+#![allow(dead_code)]
+
+#[cfg(feature = "gamepad")]
+mod gamepad;
+
+use std::{thread, time::{Duration, Instant}};
+
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use argentous_oxide::{component::{apu::CPU_CLOCK, joypad::Buttons, ppu::{DOTS_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH}}, emulator::Gameboy, lookups};
+
+const TITLE: &str = "Argentous Oxide";
+
+/// Shades 0-3 as 0RGB, after the green tint of the original screen
+const PALETTE: [u32; 4] = [0x00E0F8D0, 0x0088C070, 0x00346856, 0x00081820];
+
+/// Frames emulated per displayed frame while fast-forwarding
+const FAST_FORWARD_FRAMES: u32 = 4;
+
+/// | Key           | Button  |
+/// |---------------|---------|
+/// | X             | A       |
+/// | Z             | B       |
+/// | Backspace     | Select  |
+/// | Enter         | Start   |
+/// | Arrow keys    | D-pad   |
+const KEYMAP: [(Key, Buttons); 8] = [
+    (Key::X, Buttons::A),
+    (Key::Z, Buttons::B),
+    (Key::Backspace, Buttons::SELECT),
+    (Key::Enter, Buttons::START),
+    (Key::Right, Buttons::RIGHT),
+    (Key::Left, Buttons::LEFT),
+    (Key::Up, Buttons::UP),
+    (Key::Down, Buttons::DOWN)
+];
+
+/// | Key           | Action                  |
+/// |---------------|-------------------------|
+/// | P             | Pause/unpause           |
+/// | R             | Reset                   |
+/// | Tab (hold)    | Fast-forward            |
+/// | Escape        | Quit                    |
+const PAUSE_KEY: Key = Key::P;
+const RESET_KEY: Key = Key::R;
+const FAST_FORWARD_KEY: Key = Key::Tab;
+const QUIT_KEY: Key = Key::Escape;
+
+/// Scales the framebuffer up by an integer factor into 0RGB pixels
+fn draw(framebuffer: &[u8], buffer: &mut [u32], scale: usize)
+{
+    let width = SCREEN_WIDTH * scale;
+
+    for (y, row) in framebuffer.chunks_exact(SCREEN_WIDTH).enumerate()
+    {
+        for (x, shade) in row.iter().enumerate()
+        {
+            let color = PALETTE[(*shade & 0x03) as usize];
+
+            for dy in 0..scale
+            {
+                let start = (y * scale + dy) * width + x * scale;
+                buffer[start..start + scale].fill(color);
+            }
+        }
+    }
+}
+
+fn keyboard_buttons(window: &Window) -> Buttons
+{
+    let mut buttons = Buttons::empty();
+
+    for (key, button) in KEYMAP
+    {
+        buttons.set(button, window.is_key_down(key));
+    }
+
+    buttons
+}
+
+/// Opens a window and plays the inserted cartridge in it until the window is closed
+pub fn run(system: &mut Gameboy, scale: usize)
+{
+    let scale = scale.max(1);
+    let width = SCREEN_WIDTH * scale;
+    let height = SCREEN_HEIGHT * scale;
+
+    let mut window = match Window::new(TITLE, width, height, WindowOptions::default())
+    {
+        Ok(window) => window,
+        Err(err) => {
+            println!("Failed to open the emulator window. (Technical error: {})", err);
+            return;
+        }
+    };
+
+    // Pacing is done below, against the Gameboy's own refresh rate rather than 60Hz
+    window.set_target_fps(0);
+
+    #[cfg(feature = "gamepad")]
+    let mut gamepads = gamepad::Gamepads::new();
+
+    let frame_time = Duration::from_secs_f64(DOTS_PER_FRAME as f64 / CPU_CLOCK as f64);
+    let mut buffer = vec![0u32; width * height];
+    let mut paused = false;
+    let mut stopped = false;
+    let mut next_frame = Instant::now();
+
+    while window.is_open() && !window.is_key_down(QUIT_KEY)
+    {
+        if window.is_key_pressed(PAUSE_KEY, KeyRepeat::No)
+        {
+            paused = !paused;
+
+            match paused
+            {
+                true => window.set_title(&format!("{} (Paused)", TITLE)),
+                false => window.set_title(TITLE)
+            }
+        }
+
+        if window.is_key_pressed(RESET_KEY, KeyRepeat::No)
+        {
+            system.reset();
+            stopped = false;
+        }
+
+        #[allow(unused_mut)]
+        let mut buttons = keyboard_buttons(&window);
+
+        #[cfg(feature = "gamepad")]
+        {
+            buttons |= gamepads.poll();
+        }
+
+        system.set_buttons(buttons);
+
+        let frames = match window.is_key_down(FAST_FORWARD_KEY)
+        {
+            true => FAST_FORWARD_FRAMES,
+            false => 1
+        };
+
+        if !paused && !stopped
+        {
+            for _ in 0..frames
+            {
+                if let Err((exitcode, opcode)) = system.run_frame()
+                {
+                    println!("Gameboy routine exited with exit code {} ({} | Opcode 0x{:0>2X}). Press R to reset.", exitcode, lookups::exit_codes(exitcode), opcode);
+                    stopped = true;
+                    break;
+                }
+            }
+        }
+
+        draw(system.framebuffer(), &mut buffer, scale);
+
+        if let Err(err) = window.update_with_buffer(&buffer, width, height)
+        {
+            println!("Failed to draw to the emulator window. (Technical error: {})", err);
+            break;
+        }
+
+        // Wait out the rest of the frame. If we've fallen behind, carry on from now instead of rushing to catch up.
+        next_frame += frame_time;
+
+        match next_frame.checked_duration_since(Instant::now())
+        {
+            Some(wait) => thread::sleep(wait),
+            None => next_frame = Instant::now()
+        }
+    }
+
+    system.save_battery();
+}
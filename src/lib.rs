@@ -0,0 +1,4 @@
+pub mod component;
+pub mod cpu;
+pub mod emulator;
+pub mod lookups;
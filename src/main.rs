@@ -1,12 +1,15 @@
-use crate::{component::cartridge::Cartridge, emulator::Gameboy};
-use std::{env::current_dir, time::SystemTime};
+use argentous_oxide::{component::{apu::{wav::WavWriter, DEFAULT_SAMPLE_RATE}, cartridge::{header::CartridgeType, Cartridge, CartridgeError}, ppu::PpuBackend}, emulator::{Gameboy, Model}};
+use std::{fs, path::PathBuf, str::FromStr, time::SystemTime};
 
+#[cfg(feature = "dialog")]
+use std::env::current_dir;
+
+#[cfg(feature = "dialog")]
 use nfd::Response;
 
-mod component;
-mod emulator;
-mod lookups;
-pub mod cpu;
+mod commands;
+#[cfg(feature = "frontend")]
+mod frontend;
 
 // Copyright (c) 2021-2022 Hailey "Yuki_emeralis" Garrett [yukiemeralis@gmail.com]
 
@@ -28,45 +31,271 @@ pub mod cpu;
 // OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE
 // OR OTHER DEALINGS IN THE SOFTWARE.
 
-fn main() 
+/// Options taken from the command line
+struct Options
 {
-    let path = loop {
-        let result = nfd::open_file_dialog(None, current_dir().unwrap().to_str()).unwrap();
+    /// ROM to load. The file dialog is opened when this isn't given.
+    rom: Option<String>,
+    /// Prints every instruction as it runs
+    trace: bool,
+    /// Runs without a window, even if the frontend is built in
+    headless: bool,
+    /// Records audio to this file
+    wav: Option<PathBuf>,
+    /// Stops after this many frames
+    frames: Option<u64>,
+    /// Stops once the master clock reaches this many T-cycles
+    cycles: Option<u64>,
+    /// Runs this boot ROM before the cartridge
+    boot_rom: Option<PathBuf>,
+    /// Keeps save files here instead of next to the ROM
+    save_dir: Option<PathBuf>,
+    model: Model,
+    ppu_backend: PpuBackend,
+    /// Refuses ROMs with a bad header checksum or size, instead of warning about them
+    strict: bool,
+    /// Window size as a multiple of 160x144
+    scale: usize
+}
+
+fn print_usage()
+{
+    println!("Usage: argentous-oxide [options] [rom]");
+    println!("       argentous-oxide info [--json] <rom>");
+    println!("       argentous-oxide fix [--logo] [--dry-run] [-o <file>] <rom>");
+    println!();
+    println!("  --trace              Print every instruction as it runs. Needs --headless with the window built in.");
+    println!("  --no-trace           Don't print instructions (default)");
+    println!("  --headless           Run without a window");
+    println!("  --frames <count>     Stop after this many frames. Needs --headless with the window built in.");
+    println!("  --cycles <count>     Stop after this many T-cycles. Needs --headless with the window built in.");
+    println!("  --boot-rom <file>    Run a boot ROM before the cartridge");
+    println!("  --save-dir <dir>     Keep save files in this directory instead of next to the ROM");
+    println!("  --model <dmg|cgb>    Hardware to identify as to the game (default: dmg)");
+    println!("  --ppu <scanline|fifo> Draw each line at once, or dot by dot through the pixel FIFOs (default: scanline)");
+    println!("  --strict             Refuse ROMs with a bad header checksum or size instead of warning about them");
+    println!("  --wav <file>         Record audio to a WAV file. Implies --headless.");
+    println!("  --scale <factor>     Window size as a multiple of 160x144 (default: 4)");
+    println!("  -h, --help           Show this message");
+}
+
+/// Prints an error about the command line, and exits
+pub fn usage_error(message: String) -> !
+{
+    println!("{}", message);
+    print_usage();
+    std::process::exit(1);
+}
+
+/// Takes and parses the value following a flag
+fn flag_value<T: FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T
+{
+    let value = match args.next()
+    {
+        Some(value) => value,
+        None => usage_error(format!("Expected a value after \"{}\".", flag))
+    };
+
+    match value.parse()
+    {
+        Ok(parsed) => parsed,
+        Err(_) => usage_error(format!("Invalid value \"{}\" for \"{}\".", value, flag))
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Options
+{
+    let mut options = Options
+    {
+        rom: None,
+        trace: false,
+        headless: false,
+        wav: None,
+        frames: None,
+        cycles: None,
+        boot_rom: None,
+        save_dir: None,
+        model: Model::Dmg,
+        ppu_backend: PpuBackend::Scanline,
+        strict: false,
+        scale: 4
+    };
 
-        let filepath: String = match result
+    while let Some(arg) = args.next()
+    {
+        match arg.as_str()
         {
-            Response::Okay(f) => {
-                f
+            "--trace" => options.trace = true,
+            "--no-trace" => options.trace = false,
+            "--headless" => options.headless = true,
+            "--wav" => {
+                options.wav = Some(flag_value(&mut args, &arg));
+                options.headless = true;
+            },
+            "--frames" => options.frames = Some(flag_value(&mut args, &arg)),
+            "--cycles" => options.cycles = Some(flag_value(&mut args, &arg)),
+            "--boot-rom" => options.boot_rom = Some(flag_value(&mut args, &arg)),
+            "--save-dir" => options.save_dir = Some(flag_value(&mut args, &arg)),
+            "--model" => options.model = match flag_value::<String>(&mut args, &arg).to_lowercase().as_str()
+            {
+                "dmg" => Model::Dmg,
+                "cgb" => Model::Cgb,
+                model => usage_error(format!("Unknown model \"{}\". Expected dmg or cgb.", model))
             },
-            Response::OkayMultiple(_) => panic!(), // Not quite sure how this can fire here. Panic if it does.
-            Response::Cancel => {
-                println!("Operation cancelled. Exiting...");
+            "--ppu" => options.ppu_backend = match flag_value::<String>(&mut args, &arg).to_lowercase().as_str()
+            {
+                "scanline" => PpuBackend::Scanline,
+                "fifo" => PpuBackend::Fifo,
+                backend => usage_error(format!("Unknown PPU back end \"{}\". Expected scanline or fifo.", backend))
+            },
+            "--strict" => options.strict = true,
+            "--scale" => options.scale = flag_value(&mut args, &arg),
+            "-h" | "--help" => {
+                print_usage();
                 std::process::exit(0);
             },
-        };
-
-        if !filepath.ends_with(".gb")
-        {
-            println!("Expected file to have a .gb extension.");
-            continue;
+            _ if !arg.starts_with('-') && options.rom.is_none() => options.rom = Some(arg),
+            _ => usage_error(format!("Unexpected argument \"{}\".", arg))
         }
+    }
+
+    // The window runs until it's closed and doesn't print a trace, so these would be silently ignored
+    #[cfg(feature = "frontend")]
+    if !options.headless && (options.trace || options.frames.is_some() || options.cycles.is_some())
+    {
+        usage_error("--trace, --frames and --cycles only work with --headless.".to_string());
+    }
+
+    options
+}
+
+/// Explains why a ROM couldn't be loaded, and exits
+fn load_failed(path: &str, err: CartridgeError) -> !
+{
+    let hint = match err
+    {
+        CartridgeError::Io(_) => "The file may not exist or you do not have access to it.",
+        CartridgeError::TooShort(_) | CartridgeError::NotARom | CartridgeError::BadHeaderChecksum { .. } | CartridgeError::UnsupportedMbc(CartridgeType::Unknown(_)) => "It may not be a Gameboy ROM, or it may be corrupt.",
+        CartridgeError::UnsupportedMbc(_) => "Its memory bank controller isn't emulated.",
+        CartridgeError::SizeMismatch { .. } | CartridgeError::TooLarge(_) => "It may be a bad dump, or a patch may have changed its size."
+    };
+
+    println!("Failed to load ROM at \"{}\". {} (Technical error: {})", path, hint, err);
+    std::process::exit(1);
+}
 
-        break filepath;
+#[cfg(feature = "dialog")]
+fn pick_rom() -> String
+{
+    let result = nfd::open_file_dialog(None, current_dir().unwrap().to_str()).unwrap();
+
+    // Any file is accepted here. Whether it's actually a ROM is decided by its header when it's read.
+    match result
+    {
+        Response::Okay(f) => {
+            f
+        },
+        Response::OkayMultiple(_) => panic!(), // Not quite sure how this can fire here. Panic if it does.
+        Response::Cancel => {
+            println!("Operation cancelled. Exiting...");
+            std::process::exit(0);
+        },
+    }
+}
+
+#[cfg(not(feature = "dialog"))]
+fn pick_rom() -> String
+{
+    usage_error("No ROM given.".to_string())
+}
+
+fn main() 
+{
+    let mut args = std::env::args().skip(1).peekable();
+
+    match args.peek().map(| arg | arg.as_str())
+    {
+        Some("info") => return commands::info::run(args.skip(1)),
+        Some("fix") => return commands::fix::run(args.skip(1)),
+        _ => { }
+    }
+
+    let options = parse_args(args);
+
+    let path = match options.rom
+    {
+        Some(rom) => rom,
+        None => pick_rom()
     };
     
     let time = SystemTime::now();
     {
-        let cart: Cartridge = Cartridge::read_rom(path.as_str());
+        let mut cart: Cartridge = match Cartridge::read_rom(path.as_str())
+        {
+            Ok(cart) => cart,
+            Err(err) => load_failed(&path, err)
+        };
+
+        if options.strict
+        {
+            if let Some(err) = cart.problems().into_iter().next()
+            {
+                load_failed(&path, err);
+            }
+        }
 
         println!("Load time: {} μs\n", time.elapsed().unwrap().as_micros());
 
-        let mut system: Gameboy = Gameboy::construct();
+        if let Some(dir) = &options.save_dir
+        {
+            if let Err(err) = fs::create_dir_all(dir)
+            {
+                println!("Failed to create save directory \"{}\". (Technical error: {})", dir.display(), err);
+                std::process::exit(1);
+            }
+
+            cart.set_save_dir(dir);
+        }
+
+        let mut system: Gameboy = Gameboy::construct(options.ppu_backend, options.model);
+
+        if let Some(boot_rom) = &options.boot_rom
+        {
+            let result = fs::read(boot_rom)
+                .map_err(| err | err.to_string())
+                .and_then(| data | system.set_boot_rom(data));
+
+            if let Err(err) = result
+            {
+                println!("Failed to load boot ROM \"{}\". (Technical error: {})", boot_rom.display(), err);
+                std::process::exit(1);
+            }
+        }
 
         system.insert_cartridge(cart);
         system.read_cart_data();
 
-        system.start_cart(true);
+        if let Some(wav) = &options.wav
+        {
+            match WavWriter::create(wav, DEFAULT_SAMPLE_RATE)
+            {
+                Ok(writer) => system.set_audio_sink(Box::new(writer)),
+                Err(err) => {
+                    println!("Failed to create audio capture file \"{}\". (Technical error: {})", wav.display(), err);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        match options.headless
+        {
+            true => system.start_cart(options.trace, options.frames, options.cycles),
+            #[cfg(feature = "frontend")]
+            false => frontend::run(&mut system, options.scale),
+            #[cfg(not(feature = "frontend"))]
+            false => system.start_cart(options.trace, options.frames, options.cycles)
+        }
     }
 
     println!("Cartridge has been removed from memory.")
-}
\ No newline at end of file
+}
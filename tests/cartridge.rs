@@ -0,0 +1,76 @@
+mod common;
+
+use argentous_oxide::component::cartridge::{fix, Cartridge, CartridgeError};
+
+fn blank_rom() -> Vec<u8>
+{
+    common::blank_rom("LOADER", true)
+}
+
+#[test]
+fn loads_clean_rom_without_problems()
+{
+    let cart = Cartridge::from_bytes(blank_rom()).unwrap();
+
+    assert!(cart.problems().is_empty());
+}
+
+#[test]
+fn loads_bad_header_checksum_and_reports_it()
+{
+    let mut rom = blank_rom();
+    rom[0x14D] ^= 0xFF;
+
+    let cart = Cartridge::from_bytes(rom).unwrap();
+
+    assert!(matches!(cart.problems()[..], [CartridgeError::BadHeaderChecksum { .. }]));
+}
+
+#[test]
+fn loads_wrong_size_and_reports_it()
+{
+    let mut rom = blank_rom();
+    rom.truncate(0x6000);
+
+    let cart = Cartridge::from_bytes(rom).unwrap();
+
+    assert!(matches!(cart.problems()[..], [CartridgeError::SizeMismatch { header: 0x00, actual: 0x6000 }]));
+}
+
+#[test]
+fn rejects_rom_too_short_for_a_header()
+{
+    assert!(matches!(Cartridge::from_bytes(vec![0x00; 0x100]), Err(CartridgeError::TooShort(0x100))));
+}
+
+#[test]
+fn rejects_unsupported_controller()
+{
+    let mut rom = blank_rom();
+    rom[0x147] = 0xFC; // Pocket Camera
+    fix::fix_header_checksum(&mut rom);
+
+    assert!(matches!(Cartridge::from_bytes(rom), Err(CartridgeError::UnsupportedMbc(_))));
+}
+
+#[test]
+fn refuses_file_that_is_not_a_rom()
+{
+    let path = common::temp_rom("zeros", &[0x00; 40_000]);
+
+    let result = Cartridge::read_rom(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(result, Err(CartridgeError::NotARom)));
+}
+
+#[test]
+fn reads_rom_file_with_valid_header()
+{
+    let path = common::temp_rom("blank", &blank_rom());
+
+    let result = Cartridge::read_rom(path.to_str().unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(result.is_ok());
+}
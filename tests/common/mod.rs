@@ -0,0 +1,31 @@
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
+use argentous_oxide::component::cartridge::fix;
+
+/// A blank 32KiB ROM-only image with only a title. With `valid_header`, the Nintendo logo and header checksum are
+/// filled in too, so it loads; without, it's left for `fix` to repair.
+pub fn blank_rom(title: &str, valid_header: bool) -> Vec<u8>
+{
+    let mut rom = vec![0x00; 0x8000];
+
+    rom[0x134..0x134 + title.len()].copy_from_slice(title.as_bytes());
+
+    if valid_header
+    {
+        fix::fix_logo(&mut rom);
+        fix::fix_header_checksum(&mut rom);
+    }
+
+    rom
+}
+
+/// A file in the temp directory, unique to this test
+pub fn temp_rom(name: &str, data: &[u8]) -> PathBuf
+{
+    let path = std::env::temp_dir().join(format!("argentous-oxide-{}-{}.gb", std::process::id(), name));
+    fs::write(&path, data).unwrap();
+
+    path
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::{fs, process::Command};
+
+use argentous_oxide::component::cartridge::{fix::{self, SizeFix}, header::{self, CartridgeHeader}, CartridgeError, NINTENDO_LOGO};
+
+use common::temp_rom;
+
+/// No logo and no checksums
+fn bare_rom() -> Vec<u8>
+{
+    common::blank_rom("FIXTEST", false)
+}
+
+#[test]
+fn pads_to_next_power_of_two()
+{
+    let mut rom = bare_rom();
+    rom.resize(0x8000 + 0x4000 + 1, 0x00);
+
+    let size = fix::fix_size(&mut rom).unwrap();
+
+    assert_eq!(size, Some(SizeFix { old_len: 0xC001, new_len: 0x10000, old_code: 0x00, new_code: 0x01 }));
+    assert_eq!(rom.len(), 0x10000);
+    assert!(rom[0xC001..].iter().all(| b | *b == 0xFF));
+    assert_eq!(rom[0xC000], 0x00);
+}
+
+#[test]
+fn pads_small_rom_to_32kib()
+{
+    let mut rom = bare_rom();
+    rom.truncate(0x200);
+
+    let size = fix::fix_size(&mut rom).unwrap().unwrap();
+
+    assert_eq!(size.new_len, 0x8000);
+    assert_eq!(rom[0x148], 0x00);
+}
+
+#[test]
+fn updates_rom_size_byte()
+{
+    let mut rom = bare_rom();
+    rom.resize(0x100000, 0x00);
+
+    let size = fix::fix_size(&mut rom).unwrap().unwrap();
+
+    assert_eq!((size.old_len, size.new_len), (0x100000, 0x100000));
+    assert_eq!((size.old_code, size.new_code), (0x00, 0x05));
+    assert_eq!(CartridgeHeader::parse(&rom).unwrap().rom_size.bytes(), Some(rom.len()));
+
+    assert_eq!(fix::fix_size(&mut rom).unwrap(), None);
+}
+
+#[test]
+fn rejects_rom_over_8mib()
+{
+    let mut rom = bare_rom();
+    rom.resize(0x80_0001, 0x00);
+
+    assert!(matches!(fix::fix_size(&mut rom), Err(CartridgeError::TooLarge(0x80_0001))));
+    assert_eq!(rom.len(), 0x80_0001);
+}
+
+#[test]
+fn fixes_header_checksum()
+{
+    let mut rom = bare_rom();
+    let expected = header::header_checksum(&rom);
+
+    assert_eq!(fix::fix_header_checksum(&mut rom), Some((0x00, expected)));
+    assert_eq!(fix::fix_header_checksum(&mut rom), None);
+    assert!(CartridgeHeader::parse(&rom).unwrap().header_checksum_valid);
+}
+
+#[test]
+fn fixes_global_checksum()
+{
+    let mut rom = bare_rom();
+    let expected = header::global_checksum(&rom);
+
+    assert_eq!(fix::fix_global_checksum(&mut rom), Some((0x0000, expected)));
+    assert_eq!(fix::fix_global_checksum(&mut rom), None);
+    assert_eq!(CartridgeHeader::parse(&rom).unwrap().global_checksum, expected);
+}
+
+#[test]
+fn restores_logo()
+{
+    let mut rom = bare_rom();
+
+    assert!(fix::fix_logo(&mut rom));
+    assert_eq!(rom[0x104..=0x133], NINTENDO_LOGO);
+    assert!(!fix::fix_logo(&mut rom));
+}
+
+#[test]
+fn command_fixes_rom_in_place()
+{
+    let path = temp_rom("in-place", &bare_rom());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_argentous-oxide")).args(["fix", "--logo"]).arg(&path).output().unwrap().status;
+    let rom = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(status.success());
+    assert!(!path.with_extension("gb.tmp").exists());
+
+    let parsed = CartridgeHeader::parse(&rom).unwrap();
+    assert!(parsed.logo_valid);
+    assert!(parsed.header_checksum_valid);
+    assert_eq!(parsed.global_checksum, header::global_checksum(&rom));
+}
+
+#[test]
+fn command_leaves_logo_without_flag()
+{
+    let path = temp_rom("no-logo", &bare_rom());
+
+    let status = Command::new(env!("CARGO_BIN_EXE_argentous-oxide")).arg("fix").arg(&path).output().unwrap().status;
+    let rom = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(status.success());
+    assert!(!CartridgeHeader::parse(&rom).unwrap().logo_valid);
+}
+
+#[test]
+fn dry_run_writes_nothing()
+{
+    let mut original = bare_rom();
+    original.truncate(0x6000);
+
+    let path = temp_rom("dry-run", &original);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_argentous-oxide")).args(["fix", "--logo", "--dry-run"]).arg(&path).output().unwrap();
+    let rom = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Dry run, nothing was written."));
+    assert_eq!(rom, original);
+}
+
+#[test]
+fn command_reports_errors_on_stderr()
+{
+    let path = std::env::temp_dir().join(format!("argentous-oxide-{}-missing.gb", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_argentous-oxide")).arg("fix").arg(&path).output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Failed to read ROM"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Failed"));
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use std::path::Path;
+
+use argentous_oxide::{component::{cartridge::{fix, header::{self, CartridgeHeader}, Cartridge}, ppu::PpuBackend}, emulator::{Gameboy, Model}};
+
+/// The bundled Pokémon Blue dump, 1MiB and known good
+fn pokemon_blue() -> Vec<u8>
+{
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("rom.gb");
+    std::fs::read(path).expect("rom.gb should be in the repository root")
+}
+
+/// With a byte at the very end, so the sum has to cover the whole image
+fn blank_rom() -> Vec<u8>
+{
+    let mut rom = common::blank_rom("CHKSUM", true);
+    rom[0x7FFF] = 0xAB;
+
+    rom
+}
+
+#[test]
+fn matches_known_good_dump()
+{
+    let rom = pokemon_blue();
+    let parsed = CartridgeHeader::parse(&rom).unwrap();
+
+    assert_eq!(parsed.global_checksum, 0x9D0A);
+    assert_eq!(header::global_checksum(&rom), parsed.global_checksum);
+}
+
+#[test]
+fn emulator_agrees_with_known_good_dump()
+{
+    let mut system = Gameboy::construct(PpuBackend::Scanline, Model::Dmg);
+    system.insert_cartridge(Cartridge::from_bytes(pokemon_blue()).unwrap());
+
+    assert_eq!(system.compute_dumb_checksum(), 0x9D0A);
+}
+
+#[test]
+fn covers_every_bank()
+{
+    let mut rom = pokemon_blue();
+    let before = header::global_checksum(&rom);
+
+    // Last byte of the last bank, well past the first 64KiB
+    let last = rom.len() - 1;
+    rom[last] = rom[last].wrapping_add(1);
+
+    assert_eq!(header::global_checksum(&rom), before.wrapping_add(1));
+}
+
+#[test]
+fn skips_its_own_bytes()
+{
+    let mut rom = blank_rom();
+    let before = header::global_checksum(&rom);
+
+    rom[0x14E] = 0x12;
+    rom[0x14F] = 0x34;
+
+    assert_eq!(header::global_checksum(&rom), before);
+}
+
+#[test]
+fn works_on_32kib_rom()
+{
+    let rom = blank_rom();
+
+    let expected = rom.iter()
+        .enumerate()
+        .filter(| (i, _) | *i != 0x14E && *i != 0x14F)
+        .fold(0u16, | sum, (_, b) | sum.wrapping_add(*b as u16));
+
+    assert_eq!(header::global_checksum(&rom), expected);
+}
+
+#[test]
+fn fix_writes_a_checksum_that_verifies()
+{
+    let mut rom = blank_rom();
+
+    assert!(fix::fix_global_checksum(&mut rom).is_some());
+    assert!(fix::fix_global_checksum(&mut rom).is_none());
+
+    let parsed = CartridgeHeader::parse(&rom).unwrap();
+    assert_eq!(parsed.global_checksum, header::global_checksum(&rom));
+}